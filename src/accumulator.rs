@@ -0,0 +1,299 @@
+//! Folds the chunks of a streamed response into the response `generateContent`
+//! would have returned.
+
+use crate::{
+    Candidate, CandidateContent, CitationMetadata, GenerateContentResponseChunk, LogprobsResult,
+    Part, UsageMetadata,
+};
+use std::collections::BTreeMap;
+
+/// Merges [`GenerateContentResponseChunk`]s as they arrive:
+///
+/// - parts per candidate index, with adjacent text (or thought) parts joined
+///   and function calls kept as they are,
+/// - the last finish reason and average log probability,
+/// - safety ratings, keeping the latest probability per category,
+/// - citations, whose offsets already refer to the whole candidate text,
+///   without the duplicates repeated by later chunks,
+/// - the last usage metadata, which covers the whole response.
+#[derive(Debug, Default)]
+pub struct ResponseAccumulator {
+    candidates: BTreeMap<u32, Candidate>,
+    usage_metadata: Option<UsageMetadata>,
+}
+
+impl ResponseAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the next chunk of the stream.
+    pub fn push(&mut self, chunk: GenerateContentResponseChunk) {
+        for (position, candidate) in chunk.candidates.into_iter().enumerate() {
+            // Only set when several candidates are requested.
+            let index = candidate.index.unwrap_or(position as u32);
+            match self.candidates.get_mut(&index) {
+                Some(merged) => merge_candidate(merged, candidate),
+                None => {
+                    let mut merged = Candidate {
+                        index: candidate.index,
+                        content: None,
+                        citation_metadata: None,
+                        safety_ratings: None,
+                        finish_reason: None,
+                        avg_logprobs: None,
+                        logprobs_result: None,
+                    };
+                    merge_candidate(&mut merged, candidate);
+                    self.candidates.insert(index, merged);
+                }
+            }
+        }
+        if chunk.usage_metadata.is_some() {
+            self.usage_metadata = chunk.usage_metadata;
+        }
+    }
+
+    /// The response so far, with candidates in index order.
+    pub fn finish(self) -> GenerateContentResponseChunk {
+        GenerateContentResponseChunk {
+            candidates: self.candidates.into_values().collect(),
+            usage_metadata: self.usage_metadata,
+        }
+    }
+}
+
+fn merge_candidate(merged: &mut Candidate, candidate: Candidate) {
+    if let Some(content) = candidate.content {
+        let merged = merged.content.get_or_insert_with(|| CandidateContent {
+            role: content.role.clone(),
+            parts: Vec::new(),
+        });
+        for part in content.parts {
+            merge_part(&mut merged.parts, part);
+        }
+    }
+
+    for rating in candidate.safety_ratings.into_iter().flatten() {
+        let ratings = merged.safety_ratings.get_or_insert_with(Vec::new);
+        match ratings
+            .iter_mut()
+            .find(|merged| merged.category == rating.category)
+        {
+            Some(merged) => *merged = rating,
+            None => ratings.push(rating),
+        }
+    }
+
+    if let Some(metadata) = candidate.citation_metadata {
+        let sources = &mut merged
+            .citation_metadata
+            .get_or_insert_with(|| CitationMetadata {
+                citation_sources: Vec::new(),
+            })
+            .citation_sources;
+        for citation in metadata.citation_sources {
+            if !sources.contains(&citation) {
+                sources.push(citation);
+            }
+        }
+    }
+
+    if let Some(result) = candidate.logprobs_result {
+        let merged = merged
+            .logprobs_result
+            .get_or_insert_with(|| LogprobsResult {
+                top_candidates: Vec::new(),
+                chosen_candidates: Vec::new(),
+            });
+        merged.top_candidates.extend(result.top_candidates);
+        merged.chosen_candidates.extend(result.chosen_candidates);
+    }
+
+    if candidate.finish_reason.is_some() {
+        merged.finish_reason = candidate.finish_reason;
+    }
+    if candidate.avg_logprobs.is_some() {
+        merged.avg_logprobs = candidate.avg_logprobs;
+    }
+}
+
+fn merge_part(parts: &mut Vec<Part>, part: Part) {
+    match (parts.last_mut(), part) {
+        (Some(Part::Text(merged)), Part::Text(text)) => merged.push_str(&text),
+        (Some(Part::Thought(merged)), Part::Thought(text)) => merged.push_str(&text),
+        (_, part) => parts.push(part),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn chunk(value: serde_json::Value) -> GenerateContentResponseChunk {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn it_should_merge_chunks_per_candidate() {
+        let mut accumulator = ResponseAccumulator::new();
+        accumulator.push(chunk(json!({
+            "candidates": [
+                {"index": 0, "content": {"role": "model", "parts": [{"text": "Thinking", "thought": true}, {"text": "Hel"}]},
+                 "safetyRatings": [{"category": "HARM_CATEGORY_HARASSMENT", "probability": "NEGLIGIBLE"}]},
+                {"index": 1, "content": {"role": "model", "parts": [{"text": "Hi"}]}}
+            ],
+            "usageMetadata": {"promptTokenCount": 3, "totalTokenCount": 3}
+        })));
+        accumulator.push(chunk(json!({
+            "candidates": [
+                {"index": 0, "content": {"role": "model", "parts": [{"text": "lo"}, {"functionCall": {"name": "ls", "args": {}}}]},
+                 "safetyRatings": [{"category": "HARM_CATEGORY_HARASSMENT", "probability": "LOW"}],
+                 "citationMetadata": {"citationSources": [{"endIndex": 5, "uri": "https://example.com"}]}},
+            ]
+        })));
+        accumulator.push(chunk(json!({
+            "candidates": [
+                {"index": 1, "content": {"role": "model", "parts": [{"text": " there"}]}, "finishReason": "STOP"},
+                {"index": 0, "content": {"role": "model", "parts": [{"text": "!"}]}, "finishReason": "MAX_TOKENS",
+                 "citationMetadata": {"citationSources": [{"endIndex": 5, "uri": "https://example.com"}]}}
+            ],
+            "usageMetadata": {"promptTokenCount": 3, "candidatesTokenCount": 6, "totalTokenCount": 9}
+        })));
+
+        let response = accumulator.finish();
+        assert_eq!(response.candidates.len(), 2);
+        let first = &response.candidates[0];
+        let parts = &first.content.as_ref().unwrap().parts;
+        assert_eq!(parts.len(), 4);
+        assert!(matches!(&parts[0], Part::Thought(text) if text == "Thinking"));
+        assert!(matches!(&parts[1], Part::Text(text) if text == "Hello"));
+        assert!(matches!(&parts[2], Part::FunctionCall { name, .. } if name == "ls"));
+        assert!(matches!(&parts[3], Part::Text(text) if text == "!"));
+        assert_eq!(first.finish_reason.as_deref(), Some("MAX_TOKENS"));
+        let ratings = first.safety_ratings.as_ref().unwrap();
+        assert_eq!(ratings.len(), 1);
+        assert_eq!(ratings[0].probability, "LOW");
+        let citations = &first.citation_metadata.as_ref().unwrap().citation_sources;
+        assert_eq!(citations.len(), 1);
+        assert_eq!((citations[0].start_index, citations[0].end_index), (0, 5));
+
+        assert_eq!(response.candidates[1].index, Some(1));
+        assert_eq!(
+            response.candidates[1].finish_reason.as_deref(),
+            Some("STOP")
+        );
+        assert_eq!(response.text(), "Hello!Hi there");
+        assert_eq!(response.usage_metadata.unwrap().total_token_count, 9);
+    }
+
+    #[test]
+    fn it_should_order_candidates_by_index() {
+        let mut accumulator = ResponseAccumulator::new();
+        accumulator.push(chunk(json!({
+            "candidates": [
+                {"index": 2, "content": {"role": "model", "parts": [{"text": "c"}]}},
+                {"index": 0, "content": {"role": "model", "parts": [{"text": "a"}]}}
+            ]
+        })));
+        accumulator.push(chunk(json!({
+            "candidates": [
+                {"index": 1, "content": {"role": "model", "parts": [{"text": "b"}]}},
+                {"index": 2, "content": {"role": "model", "parts": [{"text": "C"}]}}
+            ]
+        })));
+
+        let response = accumulator.finish();
+        let texts: Vec<(Option<u32>, String)> = response
+            .candidates
+            .iter()
+            .map(|candidate| {
+                let parts = &candidate.content.as_ref().unwrap().parts;
+                let text = match &parts[..] {
+                    [Part::Text(text)] => text.clone(),
+                    parts => panic!("unexpected parts {:?}", parts),
+                };
+                (candidate.index, text)
+            })
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                (Some(0), "a".to_string()),
+                (Some(1), "b".to_string()),
+                (Some(2), "cC".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_keep_function_calls_apart() {
+        let mut accumulator = ResponseAccumulator::new();
+        accumulator.push(chunk(json!({
+            "candidates": [{"content": {"role": "model", "parts": [
+                {"text": "Let me look."},
+                {"functionCall": {"name": "list_dir", "args": {"path": "src"}}}
+            ]}}]
+        })));
+        accumulator.push(chunk(json!({
+            "candidates": [{"content": {"role": "model", "parts": [
+                {"functionCall": {"name": "read_file", "args": {"path": "src/main.rs"}}}
+            ]}}]
+        })));
+
+        let response = accumulator.finish();
+        let parts = &response.candidates[0].content.as_ref().unwrap().parts;
+        assert_eq!(parts.len(), 3);
+        assert!(matches!(&parts[0], Part::Text(text) if text == "Let me look."));
+        assert!(
+            matches!(&parts[1], Part::FunctionCall { name, args } if name == "list_dir" && args["path"] == "src")
+        );
+        assert!(matches!(&parts[2], Part::FunctionCall { name, .. } if name == "read_file"));
+    }
+
+    #[test]
+    fn it_should_take_usage_from_the_last_chunk_reporting_it() {
+        let mut accumulator = ResponseAccumulator::new();
+        accumulator.push(chunk(json!({
+            "candidates": [{"content": {"role": "model", "parts": [{"text": "Hel"}]}}]
+        })));
+        assert!(accumulator.usage_metadata.is_none());
+        accumulator.push(chunk(json!({
+            "candidates": [{"content": {"role": "model", "parts": [{"text": "lo"}]}, "finishReason": "STOP"}],
+            "usageMetadata": {"promptTokenCount": 3, "candidatesTokenCount": 2, "totalTokenCount": 5}
+        })));
+        // A trailing chunk without usage keeps the reported one.
+        accumulator.push(chunk(json!({"candidates": []})));
+
+        let usage = accumulator.finish().usage_metadata.unwrap();
+        assert_eq!(usage.candidates_token_count, Some(2));
+        assert_eq!(usage.total_token_count, 5);
+    }
+
+    #[test]
+    fn it_should_join_thoughts_separately_from_text() {
+        let mut accumulator = ResponseAccumulator::new();
+        accumulator.push(chunk(json!({
+            "candidates": [{"content": {"role": "model", "parts": [
+                {"text": "Counting ", "thought": true}
+            ]}}]
+        })));
+        accumulator.push(chunk(json!({
+            "candidates": [{"content": {"role": "model", "parts": [
+                {"text": "in French.", "thought": true},
+                {"text": "un, "}
+            ]}}]
+        })));
+        accumulator.push(chunk(json!({
+            "candidates": [{"content": {"role": "model", "parts": [{"text": "deux"}]}}]
+        })));
+
+        let response = accumulator.finish();
+        let parts = &response.candidates[0].content.as_ref().unwrap().parts;
+        assert_eq!(parts.len(), 2);
+        assert!(matches!(&parts[0], Part::Thought(text) if text == "Counting in French."));
+        assert!(matches!(&parts[1], Part::Text(text) if text == "un, deux"));
+        assert_eq!(response.text(), "un, deux");
+    }
+}
@@ -0,0 +1,255 @@
+use crate::error::Error;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::Mutex;
+
+const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const JWT_BEARER_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+
+/// Lifetime of the signed assertion, in seconds (maximum allowed by Google).
+const ASSERTION_LIFETIME: u64 = 3600;
+
+/// Tokens are refreshed this many seconds before they actually expire.
+const EXPIRY_MARGIN: u64 = 60;
+
+/// Service account key, as downloaded from the Google Cloud console.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServiceAccountKey {
+    pub project_id: Option<String>,
+    pub private_key_id: Option<String>,
+    pub private_key: String,
+    pub client_email: String,
+    pub token_uri: String,
+}
+
+impl ServiceAccountKey {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let json = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    iss: String,
+    scope: String,
+    aud: String,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Debug, Clone)]
+struct AccessToken {
+    value: String,
+    expires_at: u64,
+}
+
+/// Mints OAuth access tokens from a service account key and caches them until
+/// shortly before they expire.
+pub struct TokenSource {
+    key: ServiceAccountKey,
+    cached: Mutex<Option<AccessToken>>,
+}
+
+impl TokenSource {
+    pub fn new(key: ServiceAccountKey) -> Self {
+        Self {
+            key,
+            cached: Mutex::new(None),
+        }
+    }
+
+    pub fn key(&self) -> &ServiceAccountKey {
+        &self.key
+    }
+
+    /// Returns a valid access token, requesting a new one if necessary.
+    pub async fn token(&self, http: &reqwest::Client) -> Result<String, Error> {
+        // Holding the lock while fetching ensures concurrent callers share one refresh.
+        let mut cached = self.cached.lock().await;
+        let now = unix_time();
+
+        if let Some(token) = cached.as_ref() {
+            if token.expires_at > now + EXPIRY_MARGIN {
+                return Ok(token.value.clone());
+            }
+        }
+
+        let token = self.fetch(http, now).await?;
+        let value = token.value.clone();
+        *cached = Some(token);
+
+        Ok(value)
+    }
+
+    fn assertion(&self, now: u64) -> Result<String, Error> {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = self.key.private_key_id.clone();
+
+        let claims = Claims {
+            iss: self.key.client_email.clone(),
+            scope: CLOUD_PLATFORM_SCOPE.to_string(),
+            aud: self.key.token_uri.clone(),
+            iat: now,
+            exp: now + ASSERTION_LIFETIME,
+        };
+        let key = EncodingKey::from_rsa_pem(self.key.private_key.as_bytes())?;
+
+        Ok(jsonwebtoken::encode(&header, &claims, &key)?)
+    }
+
+    async fn fetch(&self, http: &reqwest::Client, now: u64) -> Result<AccessToken, Error> {
+        let assertion = self.assertion(now)?;
+        let res = http
+            .post(&self.key.token_uri)
+            .form(&[
+                ("grant_type", JWT_BEARER_GRANT_TYPE),
+                ("assertion", &assertion),
+            ])
+            .send()
+            .await?;
+
+        let status = res.status();
+        if !status.is_success() {
+            let body = res.text().await.unwrap_or_default();
+            return Err(Error::Auth(format!(
+                "token endpoint returned {}: {}",
+                status, body
+            )));
+        }
+
+        let token: TokenResponse = res.json().await?;
+
+        Ok(AccessToken {
+            value: token.access_token,
+            expires_at: now + token.expires_in,
+        })
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time should be after the Unix epoch")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{TestServer, TEST_PRIVATE_KEY};
+    use jsonwebtoken::{DecodingKey, Validation};
+
+    fn test_key(token_uri: String) -> ServiceAccountKey {
+        ServiceAccountKey {
+            project_id: Some("test-project".to_string()),
+            private_key_id: Some("test-key-id".to_string()),
+            private_key: TEST_PRIVATE_KEY.to_string(),
+            client_email: "test@test-project.iam.gserviceaccount.com".to_string(),
+            token_uri,
+        }
+    }
+
+    fn form_value(body: &str, name: &str) -> Option<String> {
+        body.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            (key == name).then(|| value.to_string())
+        })
+    }
+
+    #[tokio::test]
+    async fn it_should_sign_assertion() {
+        let source = TokenSource::new(test_key("https://oauth2.example.com/token".to_string()));
+        let assertion = source.assertion(1_700_000_000).unwrap();
+
+        let header = jsonwebtoken::decode_header(&assertion).unwrap();
+        assert_eq!(header.alg, Algorithm::RS256);
+        assert_eq!(header.kid.as_deref(), Some("test-key-id"));
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.insecure_disable_signature_validation();
+        validation.validate_exp = false;
+        validation.set_audience(&["https://oauth2.example.com/token"]);
+        let claims =
+            jsonwebtoken::decode::<Claims>(&assertion, &DecodingKey::from_secret(&[]), &validation)
+                .unwrap()
+                .claims;
+        assert_eq!(claims.iss, "test@test-project.iam.gserviceaccount.com");
+        assert_eq!(claims.scope, CLOUD_PLATFORM_SCOPE);
+        assert_eq!(claims.exp - claims.iat, ASSERTION_LIFETIME);
+    }
+
+    #[tokio::test]
+    async fn it_should_fetch_and_cache_token() {
+        let server = TestServer::start(|_| {
+            (
+                200,
+                r#"{"access_token": "token-1", "expires_in": 3599, "token_type": "Bearer"}"#
+                    .to_string(),
+            )
+        })
+        .await;
+        let source = TokenSource::new(test_key(server.url("/token")));
+        let http = reqwest::Client::new();
+
+        assert_eq!(source.token(&http).await.unwrap(), "token-1");
+        assert_eq!(source.token(&http).await.unwrap(), "token-1");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/token");
+        assert_eq!(
+            form_value(&requests[0].body, "grant_type").as_deref(),
+            Some("urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Ajwt-bearer")
+        );
+        assert!(form_value(&requests[0].body, "assertion").is_some());
+    }
+
+    #[tokio::test]
+    async fn it_should_refresh_expiring_token() {
+        let server = TestServer::start(|_| {
+            (
+                200,
+                format!(
+                    r#"{{"access_token": "short-lived", "expires_in": {}}}"#,
+                    EXPIRY_MARGIN
+                ),
+            )
+        })
+        .await;
+        let source = TokenSource::new(test_key(server.url("/token")));
+        let http = reqwest::Client::new();
+
+        source.token(&http).await.unwrap();
+        source.token(&http).await.unwrap();
+
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn it_should_report_token_endpoint_error() {
+        let server = TestServer::start(|_| {
+            (
+                400,
+                r#"{"error": "invalid_grant", "error_description": "Invalid JWT Signature."}"#
+                    .to_string(),
+            )
+        })
+        .await;
+        let source = TokenSource::new(test_key(server.url("/token")));
+
+        let res = source.token(&reqwest::Client::new()).await;
+        assert!(matches!(res, Err(Error::Auth(message)) if message.contains("invalid_grant")));
+    }
+}
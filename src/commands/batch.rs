@@ -0,0 +1,310 @@
+use crate::cli::BatchArgs;
+use futures_util::{
+    future,
+    stream::{self, StreamExt, TryStreamExt},
+};
+use gemini::{
+    Client, GenerateContentRequest, GenerateContentResponse, GenerationConfig, Part,
+    RequestContent, ResponseAccumulator, UsageMetadata,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchPrompt {
+    pub id: String,
+    pub prompt: String,
+}
+
+/// One line of the output file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchResult {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<UsageMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub async fn run(
+    client: &Client,
+    model: &str,
+    config: GenerationConfig,
+    no_stream: bool,
+    args: BatchArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let prompts = read_prompts(&args.input)?;
+
+    let mut completed = HashSet::new();
+    if args.resume && args.output.exists() {
+        let (kept, ids) = completed_results(&fs::read_to_string(&args.output)?);
+        fs::write(&args.output, kept)?;
+        completed = ids;
+    }
+    let mut output = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(args.resume)
+        .truncate(!args.resume)
+        .open(&args.output)?;
+
+    let pending: Vec<BatchPrompt> = prompts
+        .into_iter()
+        .filter(|prompt| !completed.contains(&prompt.id))
+        .collect();
+    if pending.is_empty() {
+        eprintln!("Nothing to do, all prompts have results.");
+        return Ok(());
+    }
+
+    crate::commands::models::validate(client, model).await?;
+
+    let total = pending.len();
+    let mut results = stream::iter(pending)
+        .map(|prompt| {
+            let config = config.clone();
+            async move {
+                let request = GenerateContentRequest {
+                    contents: vec![RequestContent {
+                        role: Some("user".to_string()),
+                        parts: vec![Part::Text(prompt.prompt)],
+                    }],
+                    generation_config: Some(config),
+                    tools: None,
+                    cached_content: None,
+                    system_instruction: None,
+                };
+                match generate(client, model, &request, no_stream).await {
+                    Ok(result) => BatchResult {
+                        id: prompt.id,
+                        ..result
+                    },
+                    Err(err) => BatchResult {
+                        id: prompt.id,
+                        error: Some(err.to_string()),
+                        ..Default::default()
+                    },
+                }
+            }
+        })
+        .buffer_unordered(args.concurrency.max(1));
+
+    let mut done = 0;
+    let mut failed = 0;
+    while let Some(result) = results.next().await {
+        done += 1;
+        writeln!(output, "{}", serde_json::to_string(&result)?)?;
+        output.flush()?;
+        match &result.error {
+            Some(err) => {
+                failed += 1;
+                eprintln!("[{}/{}] {}: {}", done, total, result.id, err);
+            }
+            None => eprintln!("[{}/{}] {}: ok", done, total, result.id),
+        }
+    }
+
+    if let Some(limiter) = client.rate_limiter() {
+        let metrics = limiter.metrics();
+        eprintln!(
+            "Waited {:.1}s for the rate limit ({} of {} requests throttled).",
+            metrics.wait_time.as_secs_f64(),
+            metrics.throttled,
+            metrics.requests
+        );
+    }
+
+    if failed > 0 {
+        return Err(format!(
+            "{} of {} prompts failed; rerun with --resume to retry them.",
+            failed, total
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Streams a response, or requests it at once with `no_stream`, and collects its
+/// text, last finish reason and usage.
+async fn generate(
+    client: &Client,
+    model: &str,
+    request: &GenerateContentRequest,
+    no_stream: bool,
+) -> Result<BatchResult, gemini::Error> {
+    let mut stream = if no_stream {
+        let response = client.generate_content(model, request).await?;
+        stream::once(future::ready(Ok(response))).boxed()
+    } else {
+        client.stream_generate_content(model, request).await?
+    };
+    let mut accumulator = ResponseAccumulator::new();
+    while let Some(item) = stream.try_next().await? {
+        match serde_json::from_value(item)? {
+            GenerateContentResponse::Chunk(chunk) => accumulator.push(chunk),
+            GenerateContentResponse::Error(err) => return Err(gemini::Error::Api(err.error)),
+        }
+    }
+
+    let response = accumulator.finish();
+    Ok(BatchResult {
+        text: Some(response.text()),
+        finish_reason: response
+            .candidates
+            .iter()
+            .find_map(|candidate| candidate.finish_reason.clone()),
+        usage: response.usage_metadata,
+        ..Default::default()
+    })
+}
+
+/// Reads a CSV file if the extension says so, JSONL otherwise.
+pub fn read_prompts(path: &Path) -> Result<Vec<BatchPrompt>, Box<dyn std::error::Error>> {
+    let file =
+        File::open(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    let is_csv = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    let prompts = if is_csv {
+        parse_csv(file)
+    } else {
+        parse_jsonl(BufReader::new(file))
+    }
+    .map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    let mut ids = HashSet::new();
+    if let Some(prompt) = prompts.iter().find(|prompt| !ids.insert(&prompt.id)) {
+        return Err(format!("{}: duplicate id {}", path.display(), prompt.id).into());
+    }
+
+    Ok(prompts)
+}
+
+/// Parses `{"id": ..., "prompt": ...}` lines; the id defaults to the line number.
+fn parse_jsonl(reader: impl BufRead) -> Result<Vec<BatchPrompt>, String> {
+    #[derive(Deserialize)]
+    struct Line {
+        id: Option<Value>,
+        prompt: String,
+    }
+
+    let mut prompts = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| err.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parsed: Line =
+            serde_json::from_str(&line).map_err(|err| format!("line {}: {}", i + 1, err))?;
+        let id = match parsed.id {
+            Some(Value::String(id)) => id,
+            Some(id) => id.to_string(),
+            None => (i + 1).to_string(),
+        };
+        prompts.push(BatchPrompt {
+            id,
+            prompt: parsed.prompt,
+        });
+    }
+
+    Ok(prompts)
+}
+
+/// Parses a CSV file with a header row containing a `prompt` and optionally
+/// an `id` column; the id defaults to the record number.
+fn parse_csv(reader: impl Read) -> Result<Vec<BatchPrompt>, String> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader.headers().map_err(|err| err.to_string())?.clone();
+    let column = |name: &str| headers.iter().position(|header| header.trim() == name);
+    let prompt_column = column("prompt").ok_or("missing prompt column")?;
+    let id_column = column("id");
+
+    let mut prompts = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record.map_err(|err| err.to_string())?;
+        let id = id_column
+            .and_then(|column| record.get(column))
+            .map(str::to_string)
+            .unwrap_or_else(|| (i + 1).to_string());
+        prompts.push(BatchPrompt {
+            id,
+            prompt: record.get(prompt_column).unwrap_or_default().to_string(),
+        });
+    }
+
+    Ok(prompts)
+}
+
+/// Keeps the successful results of a previous run, dropping failed and
+/// truncated lines, and returns them with their IDs.
+fn completed_results(output: &str) -> (String, HashSet<String>) {
+    let mut kept = String::new();
+    let mut ids = HashSet::new();
+
+    for line in output.lines() {
+        let Ok(result) = serde_json::from_str::<BatchResult>(line) else {
+            continue;
+        };
+        if result.error.is_none() {
+            kept.push_str(line);
+            kept.push('\n');
+            ids.insert(result.id);
+        }
+    }
+
+    (kept, ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_jsonl_prompts() {
+        let input = "{\"id\": \"a\", \"prompt\": \"Hello\"}\n\n{\"id\": 7, \"prompt\": \"Hi\"}\n{\"prompt\": \"Hey\"}\n";
+        let prompts = parse_jsonl(input.as_bytes()).unwrap();
+        let ids: Vec<&str> = prompts.iter().map(|prompt| prompt.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "7", "4"]);
+        assert_eq!(prompts[1].prompt, "Hi");
+    }
+
+    #[test]
+    fn it_should_parse_csv_prompts() {
+        let input = "id,prompt\nq1,\"Say \"\"hi\"\",\nthen stop\"\nq2,Count to 3\n";
+        let prompts = parse_csv(input.as_bytes()).unwrap();
+        assert_eq!(
+            prompts,
+            vec![
+                BatchPrompt {
+                    id: "q1".to_string(),
+                    prompt: "Say \"hi\",\nthen stop".to_string(),
+                },
+                BatchPrompt {
+                    id: "q2".to_string(),
+                    prompt: "Count to 3".to_string(),
+                },
+            ]
+        );
+        assert!(parse_csv("id,text\n1,Hello\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn it_should_keep_only_completed_results() {
+        let output = "{\"id\":\"a\",\"text\":\"ok\"}\n{\"id\":\"b\",\"error\":\"API error 503\"}\n{\"id\":\"c\",\"te";
+        let (kept, ids) = completed_results(output);
+        assert_eq!(kept, "{\"id\":\"a\",\"text\":\"ok\"}\n");
+        assert_eq!(ids, HashSet::from(["a".to_string()]));
+    }
+}
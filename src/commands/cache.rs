@@ -0,0 +1,105 @@
+use crate::{cli::CacheCommand, commands::files::attachment_part};
+use gemini::{client::format_ttl, CachedContent, Client, Part, RequestContent};
+use std::time::Duration;
+
+pub async fn run(
+    client: &Client,
+    model: &str,
+    command: CacheCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        CacheCommand::Create {
+            files,
+            system_instruction,
+            display_name,
+            ttl,
+        } => {
+            let mut parts = Vec::new();
+            for path in &files {
+                parts.push(attachment_part(client, path).await?);
+            }
+            let request = CachedContent {
+                model: Some(crate::commands::models::resource_name(model)),
+                display_name,
+                system_instruction: system_instruction.map(|text| RequestContent {
+                    role: None,
+                    parts: vec![Part::Text(text)],
+                }),
+                contents: Some(vec![RequestContent {
+                    role: Some("user".to_string()),
+                    parts,
+                }]),
+                ttl: Some(format_ttl(Duration::from_secs(ttl))),
+                ..Default::default()
+            };
+            print_cached_content(&client.create_cached_content(&request).await?);
+        }
+        CacheCommand::List => {
+            println!(
+                "{:<32} {:<32} {:<24} {:>8}  DISPLAY NAME",
+                "NAME", "MODEL", "EXPIRES", "TOKENS"
+            );
+            for cached_content in client.list_cached_contents().await? {
+                println!(
+                    "{:<32} {:<32} {:<24} {:>8}  {}",
+                    cached_content.name.as_deref().unwrap_or("-"),
+                    cached_content.model.as_deref().unwrap_or("-"),
+                    cached_content.expire_time.as_deref().unwrap_or("-"),
+                    total_tokens(&cached_content),
+                    cached_content.display_name.as_deref().unwrap_or("")
+                );
+            }
+        }
+        CacheCommand::Get { name } => {
+            print_cached_content(&client.get_cached_content(&name).await?)
+        }
+        CacheCommand::Update { name, ttl } => print_cached_content(
+            &client
+                .update_cached_content_ttl(&name, Duration::from_secs(ttl))
+                .await?,
+        ),
+        CacheCommand::Delete { name } => {
+            client.delete_cached_content(&name).await?;
+            println!("Deleted {}.", name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the full resource name expected by `GenerateContentRequest.cached_content`.
+pub fn resource_name(name: &str) -> String {
+    if name.starts_with("cachedContents/") {
+        name.to_string()
+    } else {
+        format!("cachedContents/{}", name)
+    }
+}
+
+fn total_tokens(cached_content: &CachedContent) -> String {
+    cached_content
+        .usage_metadata
+        .as_ref()
+        .and_then(|usage| usage.total_token_count)
+        .map_or("-".to_string(), |count| count.to_string())
+}
+
+fn print_cached_content(cached_content: &CachedContent) {
+    println!(
+        "Name:           {}",
+        cached_content.name.as_deref().unwrap_or("-")
+    );
+    if let Some(display_name) = &cached_content.display_name {
+        println!("Display name:   {}", display_name);
+    }
+    if let Some(model) = &cached_content.model {
+        println!("Model:          {}", model);
+    }
+    println!("Tokens:         {}", total_tokens(cached_content));
+    if let Some(create_time) = &cached_content.create_time {
+        println!("Created:        {}", create_time);
+    }
+    if let Some(expire_time) = &cached_content.expire_time {
+        println!("Expires:        {}", expire_time);
+    }
+}
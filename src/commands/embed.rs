@@ -0,0 +1,99 @@
+use crate::cli::{EmbedArgs, EmbedFormat};
+use gemini::{BatchEmbedContentsRequest, Client, EmbedContentRequest, Part, RequestContent};
+use serde_json::json;
+use std::io::{self, BufRead};
+
+/// Maximum number of texts the API accepts in one batch.
+const MAX_BATCH_SIZE: usize = 100;
+
+pub async fn run(client: &Client, args: EmbedArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let texts = io::stdin()
+        .lock()
+        .lines()
+        .collect::<Result<Vec<String>, _>>()?
+        .into_iter()
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<String>>();
+
+    let embeddings = embed_all(
+        client,
+        &args.embedding_model,
+        &texts,
+        args.task_type.as_deref(),
+        args.dimensions,
+    )
+    .await?;
+
+    for (text, values) in texts.iter().zip(embeddings) {
+        match args.format {
+            EmbedFormat::Json => println!("{}", json!({ "text": text, "values": values })),
+            EmbedFormat::Csv => {
+                let values = values.iter().map(|value| value.to_string());
+                let row = std::iter::once(csv_field(text))
+                    .chain(values)
+                    .collect::<Vec<String>>();
+                println!("{}", row.join(","));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Embeds all texts, split into as many batch requests as necessary.
+pub async fn embed_all(
+    client: &Client,
+    model: &str,
+    texts: &[String],
+    task_type: Option<&str>,
+    dimensions: Option<i32>,
+) -> Result<Vec<Vec<f32>>, gemini::Error> {
+    let mut embeddings = Vec::with_capacity(texts.len());
+
+    for batch in texts.chunks(MAX_BATCH_SIZE) {
+        let request = BatchEmbedContentsRequest {
+            requests: batch
+                .iter()
+                .map(|text| EmbedContentRequest {
+                    model: Some(crate::commands::models::resource_name(model)),
+                    content: RequestContent {
+                        role: None,
+                        parts: vec![Part::Text(text.clone())],
+                    },
+                    task_type: task_type.map(str::to_string),
+                    title: None,
+                    output_dimensionality: dimensions,
+                })
+                .collect(),
+        };
+        let response = client.batch_embed_contents(model, &request).await?;
+        embeddings.extend(
+            response
+                .embeddings
+                .into_iter()
+                .map(|embedding| embedding.values),
+        );
+    }
+
+    Ok(embeddings)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_quote_csv_fields() {
+        assert_eq!(csv_field("plain text"), "plain text");
+        assert_eq!(csv_field("a, b"), "\"a, b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,158 @@
+use crate::cli::FilesCommand;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use gemini::{Client, File, Part};
+use std::{fs, path::Path, time::Duration};
+
+/// Files above this size are uploaded instead of being sent inline; base64
+/// encoding grows them by a third and requests are limited to 20 MB.
+const INLINE_DATA_LIMIT: u64 = 14 * 1024 * 1024;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long an upload may stay in processing (videos take the longest).
+const MAX_PROCESSING_TIME: Duration = Duration::from_secs(10 * 60);
+
+pub async fn run(client: &Client, command: FilesCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        FilesCommand::List => {
+            println!(
+                "{:<24} {:<10} {:<24} {:>12}  DISPLAY NAME",
+                "NAME", "STATE", "MIME TYPE", "SIZE"
+            );
+            for file in client.list_files().await? {
+                println!(
+                    "{:<24} {:<10} {:<24} {:>12}  {}",
+                    file.name,
+                    file.state.as_deref().unwrap_or("-"),
+                    file.mime_type.as_deref().unwrap_or("-"),
+                    file.size_bytes.as_deref().unwrap_or("-"),
+                    file.display_name.as_deref().unwrap_or("")
+                );
+            }
+        }
+        FilesCommand::Get { name } => print_file(&client.get_file(&name).await?),
+        FilesCommand::Delete { name } => {
+            client.delete_file(&name).await?;
+            println!("Deleted {}.", name);
+        }
+        FilesCommand::Upload { path, display_name } => {
+            let display_name = display_name.or_else(|| file_name(&path));
+            let file = client
+                .upload_file(&path, guess_mime_type(&path), display_name.as_deref())
+                .await?;
+            print_file(
+                &client
+                    .wait_for_file(&file.name, POLL_INTERVAL, MAX_PROCESSING_TIME)
+                    .await?,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Turns an attached file into a part, sent inline if it is small enough and
+/// uploaded through the Files API otherwise.
+pub async fn attachment_part(
+    client: &Client,
+    path: &Path,
+) -> Result<Part, Box<dyn std::error::Error>> {
+    let mime_type = guess_mime_type(path);
+    let size = fs::metadata(path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?
+        .len();
+
+    if size <= INLINE_DATA_LIMIT {
+        return Ok(Part::InlineData {
+            mime_type: mime_type.to_string(),
+            data: STANDARD.encode(fs::read(path)?),
+        });
+    }
+
+    let file = client
+        .upload_file(path, mime_type, file_name(path).as_deref())
+        .await?;
+    let file = client
+        .wait_for_file(&file.name, POLL_INTERVAL, MAX_PROCESSING_TIME)
+        .await?;
+
+    Ok(Part::FileData {
+        mime_type: mime_type.to_string(),
+        file_uri: file.uri.ok_or("Uploaded file has no URI.")?,
+    })
+}
+
+pub fn guess_mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "heif" => "image/heif",
+        "gif" => "image/gif",
+        "pdf" => "application/pdf",
+        "mp3" => "audio/mp3",
+        "wav" => "audio/wav",
+        "aac" => "audio/aac",
+        "ogg" => "audio/ogg",
+        "flac" => "audio/flac",
+        "mp4" => "video/mp4",
+        "mpeg" | "mpg" => "video/mpeg",
+        "mov" => "video/mov",
+        "avi" => "video/avi",
+        "webm" => "video/webm",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "md" => "text/md",
+        "xml" => "text/xml",
+        "rtf" => "text/rtf",
+        "js" => "text/javascript",
+        "py" => "text/x-python",
+        _ => "text/plain",
+    }
+}
+
+fn file_name(path: &Path) -> Option<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+}
+
+fn print_file(file: &File) {
+    println!("Name:           {}", file.name);
+    if let Some(display_name) = &file.display_name {
+        println!("Display name:   {}", display_name);
+    }
+    if let Some(mime_type) = &file.mime_type {
+        println!("MIME type:      {}", mime_type);
+    }
+    if let Some(size) = &file.size_bytes {
+        println!("Size:           {} bytes", size);
+    }
+    if let Some(state) = &file.state {
+        println!("State:          {}", state);
+    }
+    if let Some(uri) = &file.uri {
+        println!("URI:            {}", uri);
+    }
+    if let Some(expiration_time) = &file.expiration_time {
+        println!("Expires:        {}", expiration_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_guess_mime_type() {
+        assert_eq!(guess_mime_type(Path::new("photo.JPG")), "image/jpeg");
+        assert_eq!(guess_mime_type(Path::new("talk.mp4")), "video/mp4");
+        assert_eq!(guess_mime_type(Path::new("notes")), "text/plain");
+    }
+}
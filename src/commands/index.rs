@@ -0,0 +1,65 @@
+use crate::{
+    cli::IndexArgs,
+    commands::embed::embed_all,
+    rag::{chunk_text, collect_files, Chunk, Index},
+};
+use gemini::Client;
+use std::{fs, path::Path};
+
+pub async fn run(
+    client: &Client,
+    index_path: &Path,
+    args: IndexArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut sources = Vec::new();
+    let mut texts = Vec::new();
+    let mut file_count = 0;
+
+    for path in collect_files(&args.dir)? {
+        // Skip binary files.
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        file_count += 1;
+        for (start_line, end_line, chunk) in chunk_text(&text, args.chunk_size) {
+            sources.push((path.display().to_string(), start_line, end_line));
+            texts.push(chunk);
+        }
+    }
+
+    let embeddings = embed_all(
+        client,
+        &args.embedding_model,
+        &texts,
+        Some("RETRIEVAL_DOCUMENT"),
+        None,
+    )
+    .await?;
+
+    let chunks = sources
+        .into_iter()
+        .zip(texts)
+        .zip(embeddings)
+        .map(|(((source, start_line, end_line), text), values)| Chunk {
+            source,
+            start_line,
+            end_line,
+            text,
+            values,
+        })
+        .collect::<Vec<Chunk>>();
+
+    let chunk_count = chunks.len();
+    Index {
+        model: args.embedding_model,
+        chunks,
+    }
+    .save(index_path)?;
+    println!(
+        "Indexed {} chunks from {} files into {}.",
+        chunk_count,
+        file_count,
+        index_path.display()
+    );
+    Ok(())
+}
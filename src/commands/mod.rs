@@ -0,0 +1,7 @@
+pub mod batch;
+pub mod cache;
+pub mod embed;
+pub mod files;
+pub mod index;
+pub mod models;
+pub mod serve;
@@ -0,0 +1,99 @@
+use crate::cli::ModelsCommand;
+use gemini::{Client, Model};
+
+pub async fn run(
+    client: &Client,
+    command: ModelsCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        ModelsCommand::List => {
+            println!("{:<40} {:>8} {:>8}  METHODS", "NAME", "INPUT", "OUTPUT");
+            for model in client.list_models().await? {
+                println!(
+                    "{:<40} {:>8} {:>8}  {}",
+                    model.id(),
+                    format_limit(model.input_token_limit),
+                    format_limit(model.output_token_limit),
+                    model.supported_generation_methods.join(", ")
+                );
+            }
+        }
+        ModelsCommand::Get { name } => print_model(&client.get_model(&name).await?),
+    }
+
+    Ok(())
+}
+
+/// Returns the full resource name of a model, e.g. `models/text-embedding-004`.
+pub fn resource_name(model: &str) -> String {
+    if model.starts_with("models/") {
+        model.to_string()
+    } else {
+        format!("models/{}", model)
+    }
+}
+
+/// Checks that the model exists and can generate content before sending a request.
+pub async fn validate(client: &Client, model: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match client.get_model(model).await {
+        Ok(info) if info.supports("generateContent") => Ok(()),
+        Ok(_) => Err(format!("Model {} does not support generateContent.", model).into()),
+        // Model metadata is not available on every backend.
+        Err(gemini::Error::Unsupported(_)) => Ok(()),
+        Err(gemini::Error::Api(details)) if details.code == 404 => Err(format!(
+            "Unknown model {}, see `gemini models list` for available models.",
+            model
+        )
+        .into()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn print_model(model: &Model) {
+    println!("Name:           {}", model.id());
+    if let Some(display_name) = &model.display_name {
+        println!("Display name:   {}", display_name);
+    }
+    if let Some(version) = &model.version {
+        println!("Version:        {}", version);
+    }
+    if let Some(description) = &model.description {
+        println!("Description:    {}", description);
+    }
+    println!("Input tokens:   {}", format_limit(model.input_token_limit));
+    println!("Output tokens:  {}", format_limit(model.output_token_limit));
+    println!(
+        "Methods:        {}",
+        model.supported_generation_methods.join(", ")
+    );
+    if let Some(temperature) = model.temperature {
+        println!("Temperature:    {}", temperature);
+    }
+    if let Some(top_p) = model.top_p {
+        println!("Top P:          {}", top_p);
+    }
+    if let Some(top_k) = model.top_k {
+        println!("Top K:          {}", top_k);
+    }
+}
+
+fn format_limit(limit: Option<i32>) -> String {
+    limit.map_or("-".to_string(), |limit| limit.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_prefix_model_resource_name_once() {
+        assert_eq!(
+            resource_name("text-embedding-004"),
+            "models/text-embedding-004"
+        );
+        assert_eq!(
+            resource_name("models/text-embedding-004"),
+            "models/text-embedding-004"
+        );
+    }
+}
@@ -0,0 +1,619 @@
+//! OpenAI-compatible HTTP server translating chat completions and embeddings
+//! into Gemini requests, for tools that only speak the OpenAI API.
+
+use crate::{cli::ServeArgs, commands::embed::embed_all};
+use chrono::Utc;
+use futures_util::stream::TryStreamExt;
+use gemini::{
+    Candidate, Client, GenerateContentRequest, GenerateContentResponse,
+    GenerateContentResponseChunk, GenerationConfig, Part, RequestContent, ResponseAccumulator,
+    UsageMetadata,
+};
+use hyper::{
+    body::{self, Bytes},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+struct State {
+    client: Client,
+    model: String,
+    embedding_model: String,
+    next_id: AtomicU64,
+}
+
+pub async fn run(
+    client: Client,
+    model: String,
+    args: ServeArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let addr: SocketAddr = format!("{}:{}", args.host, args.port).parse()?;
+    let state = Arc::new(State {
+        client,
+        model,
+        embedding_model: args.embedding_model,
+        next_id: AtomicU64::new(1),
+    });
+
+    let make_service = make_service_fn(move |_| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let state = state.clone();
+                async move { Ok::<_, Infallible>(handle(&state, request).await) }
+            }))
+        }
+    });
+    let server = Server::try_bind(&addr)?.serve(make_service);
+    eprintln!("Listening on http://{}/v1", server.local_addr());
+    server.await?;
+
+    Ok(())
+}
+
+async fn handle(state: &State, request: Request<Body>) -> Response<Body> {
+    let route = (request.method().clone(), request.uri().path().to_string());
+    let body = match body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(err) => return error_response(StatusCode::BAD_REQUEST, &err.to_string()),
+    };
+
+    match (route.0, route.1.trim_end_matches('/')) {
+        (Method::POST, "/v1/chat/completions") => match serde_json::from_slice(&body) {
+            Ok(request) => chat_completions(state, request).await,
+            Err(err) => error_response(StatusCode::BAD_REQUEST, &err.to_string()),
+        },
+        (Method::POST, "/v1/embeddings") => match serde_json::from_slice(&body) {
+            Ok(request) => embeddings(state, request).await,
+            Err(err) => error_response(StatusCode::BAD_REQUEST, &err.to_string()),
+        },
+        (_, path) => error_response(StatusCode::NOT_FOUND, &format!("Unknown endpoint {}", path)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionRequest {
+    #[serde(default)]
+    model: String,
+    messages: Vec<ChatMessage>,
+    #[serde(default)]
+    stream: bool,
+    stream_options: Option<StreamOptions>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    max_tokens: Option<i32>,
+    max_completion_tokens: Option<i32>,
+    stop: Option<Stop>,
+    n: Option<i32>,
+    presence_penalty: Option<f32>,
+    frequency_penalty: Option<f32>,
+    seed: Option<i32>,
+    response_format: Option<ResponseFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamOptions {
+    #[serde(default)]
+    include_usage: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatMessage {
+    role: String,
+    /// `None` for messages without text, e.g. assistant messages with `tool_calls`.
+    #[serde(default)]
+    content: Option<MessageContent>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageUrl {
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Stop {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseFormat {
+    r#type: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingRequest {
+    #[serde(default)]
+    model: String,
+    input: EmbeddingInput,
+    dimensions: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum EmbeddingInput {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Maps the chat messages and parameters to a Gemini request: system messages
+/// become the system instruction and the assistant is the model. Messages
+/// without content are left out, as Gemini rejects contents without parts.
+fn to_gemini_request(request: &ChatCompletionRequest) -> Result<GenerateContentRequest, String> {
+    let mut system = Vec::new();
+    let mut contents = Vec::new();
+    for message in &request.messages {
+        let parts = match &message.content {
+            Some(content) => message_parts(content)?,
+            None => Vec::new(),
+        };
+        match message.role.as_str() {
+            "system" | "developer" | "user" | "assistant" if parts.is_empty() => {}
+            "system" | "developer" => system.extend(parts),
+            "user" => contents.push(RequestContent {
+                role: Some("user".to_string()),
+                parts,
+            }),
+            "assistant" => contents.push(RequestContent {
+                role: Some("model".to_string()),
+                parts,
+            }),
+            role => return Err(format!("Unsupported message role {}", role)),
+        }
+    }
+
+    let stop_sequences = match &request.stop {
+        Some(Stop::One(stop)) => Some(vec![stop.clone()]),
+        Some(Stop::Many(stops)) if !stops.is_empty() => Some(stops.clone()),
+        _ => None,
+    };
+    let response_mime_type = match request.response_format.as_ref().map(|f| f.r#type.as_str()) {
+        Some("json_object") => Some("application/json".to_string()),
+        Some("text") | None => None,
+        Some(other) => return Err(format!("Unsupported response format {}", other)),
+    };
+
+    Ok(GenerateContentRequest {
+        contents,
+        generation_config: Some(GenerationConfig {
+            max_output_tokens: request.max_completion_tokens.or(request.max_tokens),
+            temperature: request.temperature,
+            top_p: request.top_p,
+            stop_sequences,
+            candidate_count: request.n,
+            presence_penalty: request.presence_penalty,
+            frequency_penalty: request.frequency_penalty,
+            seed: request.seed,
+            response_mime_type,
+            ..Default::default()
+        }),
+        tools: None,
+        cached_content: None,
+        system_instruction: (!system.is_empty()).then_some(RequestContent {
+            role: None,
+            parts: system,
+        }),
+    })
+}
+
+fn message_parts(content: &MessageContent) -> Result<Vec<Part>, String> {
+    let parts = match content {
+        MessageContent::Text(text) => return Ok(vec![Part::Text(text.clone())]),
+        MessageContent::Parts(parts) => parts,
+    };
+    parts
+        .iter()
+        .map(|part| match part {
+            ContentPart::Text { text } => Ok(Part::Text(text.clone())),
+            // Only inline images, e.g. `data:image/png;base64,iVBOR...`.
+            ContentPart::ImageUrl { image_url } => image_url
+                .url
+                .strip_prefix("data:")
+                .and_then(|url| url.split_once(";base64,"))
+                .map(|(mime_type, data)| Part::InlineData {
+                    mime_type: mime_type.to_string(),
+                    data: data.to_string(),
+                })
+                .ok_or_else(|| "Only base64 data URLs are supported for images".to_string()),
+        })
+        .collect()
+}
+
+fn finish_reason(reason: &str) -> &'static str {
+    match reason {
+        "MAX_TOKENS" => "length",
+        "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" => "content_filter",
+        _ => "stop",
+    }
+}
+
+fn usage(metadata: &UsageMetadata) -> Value {
+    let completion_tokens =
+        metadata.candidates_token_count.unwrap_or(0) + metadata.thoughts_token_count.unwrap_or(0);
+    json!({
+        "prompt_tokens": metadata.prompt_token_count,
+        "completion_tokens": completion_tokens,
+        "total_tokens": metadata.total_token_count,
+    })
+}
+
+/// The answer text of a candidate, without thoughts.
+fn candidate_text(candidate: &Candidate) -> String {
+    candidate
+        .content
+        .iter()
+        .flat_map(|content| content.parts.iter())
+        .filter_map(|part| match part {
+            Part::Text(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Choices of a streamed chunk; the first delta of each choice carries the role.
+fn chunk_choices(chunk: &GenerateContentResponseChunk, started: &mut Vec<u32>) -> Vec<Value> {
+    chunk
+        .candidates
+        .iter()
+        .map(|candidate| {
+            let index = candidate.index.unwrap_or(0);
+            let mut delta = json!({ "content": candidate_text(candidate) });
+            if !started.contains(&index) {
+                started.push(index);
+                delta["role"] = json!("assistant");
+            }
+            json!({
+                "index": index,
+                "delta": delta,
+                "finish_reason": candidate.finish_reason.as_deref().map(finish_reason),
+            })
+        })
+        .collect()
+}
+
+/// Choices of the whole response, for requests without streaming.
+fn response_choices(response: &GenerateContentResponseChunk) -> Vec<Value> {
+    response
+        .candidates
+        .iter()
+        .map(|candidate| {
+            json!({
+                "index": candidate.index.unwrap_or(0),
+                "message": { "role": "assistant", "content": candidate_text(candidate) },
+                "finish_reason": candidate.finish_reason.as_deref().map_or("stop", finish_reason),
+            })
+        })
+        .collect()
+}
+
+async fn chat_completions(state: &State, request: ChatCompletionRequest) -> Response<Body> {
+    let gemini_request = match to_gemini_request(&request) {
+        Ok(gemini_request) => gemini_request,
+        Err(err) => return error_response(StatusCode::BAD_REQUEST, &err),
+    };
+    let model = model_name(&request.model, &state.model);
+    let mut stream = match state
+        .client
+        .stream_generate_content(&model, &gemini_request)
+        .await
+    {
+        Ok(stream) => stream,
+        Err(err) => return upstream_error(err),
+    };
+
+    let id = format!("chatcmpl-{}", state.next_id.fetch_add(1, Ordering::Relaxed));
+    let created = Utc::now().timestamp();
+
+    if request.stream {
+        let include_usage = request.stream_options.is_some_and(|o| o.include_usage);
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            let mut started = Vec::new();
+            let mut last_usage = None;
+            loop {
+                let event = match stream.try_next().await {
+                    Ok(Some(item)) => match serde_json::from_value(item) {
+                        Ok(GenerateContentResponse::Chunk(chunk)) => {
+                            if chunk.usage_metadata.is_some() {
+                                last_usage = chunk.usage_metadata.clone();
+                            }
+                            json!({
+                                "id": id,
+                                "object": "chat.completion.chunk",
+                                "created": created,
+                                "model": model,
+                                "choices": chunk_choices(&chunk, &mut started),
+                            })
+                        }
+                        Ok(GenerateContentResponse::Error(err)) => {
+                            json!({ "error": { "message": err.error.message } })
+                        }
+                        Err(err) => json!({ "error": { "message": err.to_string() } }),
+                    },
+                    Ok(None) => break,
+                    Err(err) => json!({ "error": { "message": err.to_string() } }),
+                };
+                if sender.send_data(sse_event(&event)).await.is_err() {
+                    return;
+                }
+                if event.get("error").is_some() {
+                    // Clients wait for the end of the stream even after an error.
+                    let _ = sender.send_data(Bytes::from("data: [DONE]\n\n")).await;
+                    return;
+                }
+            }
+            if let (true, Some(metadata)) = (include_usage, &last_usage) {
+                let event = json!({
+                    "id": id,
+                    "object": "chat.completion.chunk",
+                    "created": created,
+                    "model": model,
+                    "choices": [],
+                    "usage": usage(metadata),
+                });
+                let _ = sender.send_data(sse_event(&event)).await;
+            }
+            let _ = sender.send_data(Bytes::from("data: [DONE]\n\n")).await;
+        });
+
+        return Response::builder()
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .body(body)
+            .unwrap();
+    }
+
+    let mut accumulator = ResponseAccumulator::new();
+    loop {
+        let item = match stream.try_next().await {
+            Ok(Some(item)) => item,
+            Ok(None) => break,
+            Err(err) => return upstream_error(err),
+        };
+        let chunk = match serde_json::from_value(item) {
+            Ok(GenerateContentResponse::Chunk(chunk)) => chunk,
+            Ok(GenerateContentResponse::Error(err)) => {
+                return error_response(StatusCode::BAD_GATEWAY, &err.error.message)
+            }
+            Err(err) => return error_response(StatusCode::BAD_GATEWAY, &err.to_string()),
+        };
+        accumulator.push(chunk);
+    }
+
+    let response = accumulator.finish();
+    json_response(
+        StatusCode::OK,
+        &json!({
+            "id": id,
+            "object": "chat.completion",
+            "created": created,
+            "model": model,
+            "choices": response_choices(&response),
+            "usage": response.usage_metadata.as_ref().map(usage),
+        }),
+    )
+}
+
+async fn embeddings(state: &State, request: EmbeddingRequest) -> Response<Body> {
+    let texts = match request.input {
+        EmbeddingInput::One(text) => vec![text],
+        EmbeddingInput::Many(texts) => texts,
+    };
+    let model = model_name(&request.model, &state.embedding_model);
+    let embeddings = match embed_all(&state.client, &model, &texts, None, request.dimensions).await
+    {
+        Ok(embeddings) => embeddings,
+        Err(err) => return upstream_error(err),
+    };
+
+    let data: Vec<Value> = embeddings
+        .into_iter()
+        .enumerate()
+        .map(
+            |(index, values)| json!({ "object": "embedding", "index": index, "embedding": values }),
+        )
+        .collect();
+    json_response(
+        StatusCode::OK,
+        &json!({
+            "object": "list",
+            "data": data,
+            "model": model,
+            // Embedding requests report no token counts.
+            "usage": { "prompt_tokens": 0, "total_tokens": 0 },
+        }),
+    )
+}
+
+/// The requested model without the `models/` prefix, or the default.
+fn model_name(requested: &str, default: &str) -> String {
+    let requested = requested.trim_start_matches("models/");
+    if requested.is_empty() {
+        default.to_string()
+    } else {
+        requested.to_string()
+    }
+}
+
+fn sse_event(event: &Value) -> Bytes {
+    Bytes::from(format!("data: {}\n\n", event))
+}
+
+fn json_response(status: StatusCode, body: &Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+    let r#type = if status.is_client_error() {
+        "invalid_request_error"
+    } else {
+        "api_error"
+    };
+    json_response(
+        status,
+        &json!({ "error": { "message": message, "type": r#type, "code": status.as_u16() } }),
+    )
+}
+
+/// Passes on the status of API errors, e.g. 429 for exhausted quotas.
+fn upstream_error(err: gemini::Error) -> Response<Body> {
+    let status = match &err {
+        gemini::Error::Api(details) => u16::try_from(details.code).ok(),
+        gemini::Error::Status(status, _) => Some(*status),
+        _ => None,
+    }
+    .and_then(|status| StatusCode::from_u16(status).ok())
+    .unwrap_or(StatusCode::BAD_GATEWAY);
+    error_response(status, &err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_translate_chat_request() {
+        let request: ChatCompletionRequest = serde_json::from_value(json!({
+            "model": "gemini-1.5-flash",
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": "Hi"},
+                {"role": "assistant", "content": "Hello!"},
+                {"role": "assistant", "content": null, "tool_calls": []},
+                {"role": "user", "content": [
+                    {"type": "text", "text": "What is this?"},
+                    {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBOR"}}
+                ]}
+            ],
+            "max_tokens": 100,
+            "stop": "END",
+            "response_format": {"type": "json_object"}
+        }))
+        .unwrap();
+
+        assert_eq!(
+            json!(to_gemini_request(&request).unwrap()),
+            json!({
+                "contents": [
+                    {"role": "user", "parts": [{"text": "Hi"}]},
+                    {"role": "model", "parts": [{"text": "Hello!"}]},
+                    {"role": "user", "parts": [
+                        {"text": "What is this?"},
+                        {"inlineData": {"mime_type": "image/png", "data": "iVBOR"}}
+                    ]}
+                ],
+                "generation_config": {
+                    "maxOutputTokens": 100,
+                    "stopSequences": ["END"],
+                    "responseMimeType": "application/json"
+                },
+                "system_instruction": {"parts": [{"text": "Be brief."}]}
+            })
+        );
+
+        let remote_image: ChatCompletionRequest = serde_json::from_value(json!({
+            "messages": [{"role": "user", "content": [
+                {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}}
+            ]}]
+        }))
+        .unwrap();
+        assert!(to_gemini_request(&remote_image).is_err());
+    }
+
+    #[test]
+    fn it_should_translate_streamed_chunks() {
+        let chunk: GenerateContentResponseChunk = serde_json::from_value(json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "Hel"}]}
+            }]
+        }))
+        .unwrap();
+        let last: GenerateContentResponseChunk = serde_json::from_value(json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "lo"}]},
+                "finishReason": "MAX_TOKENS"
+            }],
+            "usageMetadata": {"promptTokenCount": 3, "candidatesTokenCount": 2, "totalTokenCount": 5}
+        }))
+        .unwrap();
+
+        let mut started = Vec::new();
+        assert_eq!(
+            chunk_choices(&chunk, &mut started),
+            vec![json!({
+                "index": 0,
+                "delta": {"role": "assistant", "content": "Hel"},
+                "finish_reason": null
+            })]
+        );
+        assert_eq!(
+            chunk_choices(&last, &mut started),
+            vec![json!({"index": 0, "delta": {"content": "lo"}, "finish_reason": "length"})]
+        );
+        assert_eq!(
+            usage(last.usage_metadata.as_ref().unwrap()),
+            json!({"prompt_tokens": 3, "completion_tokens": 2, "total_tokens": 5})
+        );
+    }
+
+    #[test]
+    fn it_should_translate_whole_response() {
+        let mut accumulator = ResponseAccumulator::new();
+        for chunk in [
+            json!({"candidates": [
+                {"index": 0, "content": {"role": "model", "parts": [{"text": "Hel"}]}},
+                {"index": 1, "content": {"role": "model", "parts": [{"text": "Hi"}]}}
+            ]}),
+            json!({"candidates": [
+                {"index": 0, "content": {"role": "model", "parts": [{"text": "lo"}]}, "finishReason": "MAX_TOKENS"}
+            ]}),
+        ] {
+            accumulator.push(serde_json::from_value(chunk).unwrap());
+        }
+
+        assert_eq!(
+            response_choices(&accumulator.finish()),
+            vec![
+                json!({
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Hello"},
+                    "finish_reason": "length"
+                }),
+                json!({
+                    "index": 1,
+                    "message": {"role": "assistant", "content": "Hi"},
+                    "finish_reason": "stop"
+                }),
+            ]
+        );
+    }
+}
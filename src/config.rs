@@ -0,0 +1,127 @@
+use gemini::{ApiVersion, StreamFormat};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Settings read from the configuration file; command-line options take precedence.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub base_url: Option<String>,
+    pub api_version: Option<ApiVersion>,
+    pub stream_format: Option<StreamFormat>,
+    // Timeouts in seconds.
+    pub connect_timeout: Option<u64>,
+    pub read_timeout: Option<u64>,
+    pub timeout: Option<u64>,
+    pub idle_timeout: Option<u64>,
+    pub proxy: Option<String>,
+    pub ca_bundle: Option<PathBuf>,
+    // Client-side rate limits.
+    pub rpm: Option<u32>,
+    pub tpm: Option<u32>,
+    /// Directory --template names are looked up in [default: ~/.config/gemini/templates].
+    pub templates_dir: Option<PathBuf>,
+    /// Commands --tools may run without confirmation, e.g. `["ls", "cargo test"]`.
+    pub allowed_commands: Vec<String>,
+    /// MCP servers --mcp can enable, by name.
+    pub mcp_servers: HashMap<String, McpServerConfig>,
+}
+
+/// An MCP server run as a child process speaking JSON-RPC over stdio, e.g.
+///
+/// ```toml
+/// [mcp_servers.tickets]
+/// command = "tickets-mcp"
+/// args = ["--stdio"]
+/// env = { TICKETS_TOKEN = "..." }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct McpServerConfig {
+    pub command: String,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+    /// Call the tools without confirmation, not only those marked read-only.
+    pub trusted: bool,
+}
+
+impl Config {
+    /// Loads the given file, or the default one if it exists.
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Config::default()),
+            },
+        };
+
+        let text = fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        toml::from_str(&text)
+            .map_err(|err| format!("Invalid config {}: {}", path.display(), err).into())
+    }
+}
+
+impl Config {
+    pub fn templates_dir(&self) -> PathBuf {
+        self.templates_dir
+            .clone()
+            .or_else(|| config_dir().map(|dir| dir.join("templates")))
+            .unwrap_or_else(|| PathBuf::from("templates"))
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("gemini"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            base_url = "http://localhost:8080"
+            api_version = "v1"
+            stream_format = "sse"
+            idle_timeout = 30
+            proxy = "http://proxy.example.com:3128"
+            ca_bundle = "/etc/ssl/corporate.pem"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.base_url.as_deref(), Some("http://localhost:8080"));
+        assert_eq!(config.api_version, Some(ApiVersion::V1));
+        assert_eq!(config.stream_format, Some(StreamFormat::Sse));
+        assert_eq!(config.idle_timeout, Some(30));
+        assert_eq!(
+            config.proxy.as_deref(),
+            Some("http://proxy.example.com:3128")
+        );
+        assert_eq!(
+            config.ca_bundle,
+            Some(PathBuf::from("/etc/ssl/corporate.pem"))
+        );
+    }
+
+    #[test]
+    fn it_should_default_to_empty_config() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config.base_url.is_none());
+        assert!(config.api_version.is_none());
+    }
+}
@@ -0,0 +1,78 @@
+use crate::GenerateContentResponseErrorDetails;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Http(reqwest::Error),
+    Stream(reqwest_streams::error::StreamBodyError),
+    /// Malformed Server-Sent Events stream.
+    Sse(String),
+    Jwt(jsonwebtoken::errors::Error),
+    Auth(String),
+    Timeout(String),
+    Api(GenerateContentResponseErrorDetails),
+    Status(u16, String),
+    Unsupported(String),
+    Schema(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            Error::Json(err) => write!(f, "JSON error: {}", err),
+            Error::Http(err) => write!(f, "HTTP error: {}", err),
+            Error::Stream(err) => write!(f, "Stream error: {}", err),
+            Error::Sse(message) => write!(f, "Event stream error: {}", message),
+            Error::Jwt(err) => write!(f, "JWT error: {}", err),
+            Error::Auth(message) => write!(f, "Authentication failed: {}", message),
+            Error::Timeout(message) => write!(f, "Timed out: {}", message),
+            Error::Api(details) => write!(
+                f,
+                "API error {} ({}): {}",
+                details.code, details.status, details.message
+            ),
+            Error::Status(status, body) => {
+                write!(f, "Unexpected response status {}: {}", status, body)
+            }
+            Error::Unsupported(message) => write!(f, "Not supported: {}", message),
+            Error::Schema(message) => {
+                write!(f, "Response does not match the schema: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http(err)
+    }
+}
+
+impl From<reqwest_streams::error::StreamBodyError> for Error {
+    fn from(err: reqwest_streams::error::StreamBodyError) -> Self {
+        Error::Stream(err)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for Error {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        Error::Jwt(err)
+    }
+}
@@ -0,0 +1,308 @@
+//! Writes the fenced code blocks of a response to files.
+
+use crate::cli::ConflictPolicy;
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+#[derive(Debug, PartialEq)]
+pub struct CodeBlock {
+    pub language: String,
+    /// File name given by the info string or a `file:` comment.
+    pub file_name: Option<String>,
+    pub code: String,
+}
+
+/// Finds the fenced code blocks of a Markdown text. A file name may follow the
+/// language (```` ```rust src/main.rs ````) or be given by a comment on the first
+/// line (`// file: src/main.rs`), which is then left out of the code.
+pub fn parse_code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut lines = text.lines();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        let Some(fence_char) = ['`', '~']
+            .into_iter()
+            .find(|c| trimmed.starts_with(&c.to_string().repeat(3)))
+        else {
+            continue;
+        };
+        let fence_len = trimmed.len() - trimmed.trim_start_matches(fence_char).len();
+        let fence = &trimmed[..fence_len];
+        let mut info = trimmed[fence_len..].split_whitespace();
+        let language = info.next().unwrap_or_default();
+        let (language, mut file_name) = match language.split_once(':') {
+            Some((language, path)) => (language.to_string(), Some(path.to_string())),
+            None => (language.to_string(), None),
+        };
+        if let Some(path) = info.next() {
+            file_name = Some(
+                path.trim_start_matches("title=")
+                    .trim_matches('"')
+                    .to_string(),
+            );
+        }
+
+        let mut code_lines = Vec::new();
+        for line in lines.by_ref() {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with(fence) && trimmed.trim_end().chars().all(|c| c == fence_char) {
+                break;
+            }
+            code_lines.push(line);
+        }
+
+        if let Some(hint) = code_lines.first().and_then(|line| file_hint(line)) {
+            file_name.get_or_insert(hint);
+            code_lines.remove(0);
+        }
+        let mut code = code_lines.join("\n");
+        code.push('\n');
+
+        blocks.push(CodeBlock {
+            language,
+            file_name,
+            code,
+        });
+    }
+
+    blocks
+}
+
+/// Reads a `file:` hint from a comment such as `// file: a.rs`, `# file: a.py`
+/// or `<!-- file: a.html -->`.
+fn file_hint(line: &str) -> Option<String> {
+    let line = line.trim();
+    let comment = ["//", "#", "--", ";", "<!--", "/*"]
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))?;
+    let comment = comment
+        .trim_end_matches("-->")
+        .trim_end_matches("*/")
+        .trim();
+    let name = comment
+        .strip_prefix("file:")
+        .or_else(|| comment.strip_prefix("filename:"))?
+        .trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// File extension for a language tag, e.g. `py` for `python`.
+fn extension(language: &str) -> &str {
+    match language.to_lowercase().as_str() {
+        "rust" | "rs" => "rs",
+        "python" | "py" => "py",
+        "javascript" | "js" | "node" => "js",
+        "typescript" | "ts" => "ts",
+        "tsx" => "tsx",
+        "jsx" => "jsx",
+        "go" | "golang" => "go",
+        "java" => "java",
+        "kotlin" | "kt" => "kt",
+        "c" => "c",
+        "cpp" | "c++" | "cxx" => "cpp",
+        "csharp" | "c#" | "cs" => "cs",
+        "ruby" | "rb" => "rb",
+        "php" => "php",
+        "swift" => "swift",
+        "bash" | "sh" | "shell" | "zsh" => "sh",
+        "html" => "html",
+        "css" => "css",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "sql" => "sql",
+        "markdown" | "md" => "md",
+        "dockerfile" => "Dockerfile",
+        _ => "txt",
+    }
+}
+
+/// Path of a block relative to the output directory; hinted names must stay
+/// inside it.
+fn relative_path(block: &CodeBlock, index: usize) -> Result<PathBuf, String> {
+    let Some(name) = &block.file_name else {
+        return Ok(PathBuf::from(format!(
+            "snippet-{}.{}",
+            index + 1,
+            extension(&block.language)
+        )));
+    };
+
+    let path = PathBuf::from(name);
+    check_relative(&path)?;
+    Ok(path)
+}
+
+/// Rejects absolute paths and paths leaving their base directory, e.g. file
+/// names proposed by the model.
+pub fn check_relative(path: &Path) -> Result<(), String> {
+    if path
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(format!(
+            "{}: refusing to write outside the working directory",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// `name.1.ext`, `name.2.ext`, ... for the first path that does not exist.
+fn free_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|ext| ext.to_string_lossy());
+    (1..)
+        .map(|n| {
+            let name = match &extension {
+                Some(ext) => format!("{}.{}.{}", stem, n, ext),
+                None => format!("{}.{}", stem, n),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+/// Writes the code blocks of the response into `dir`, or only lists what would
+/// be written, reporting each file on stderr.
+pub fn extract_code(
+    text: &str,
+    dir: &Path,
+    on_conflict: ConflictPolicy,
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let blocks = parse_code_blocks(text);
+    if blocks.is_empty() {
+        eprintln!("No code blocks to extract.");
+        return Ok(());
+    }
+
+    for (index, block) in blocks.iter().enumerate() {
+        let path = match relative_path(block, index) {
+            Ok(path) => dir.join(path),
+            Err(err) => {
+                eprintln!("Skipped block {}: {}", index + 1, err);
+                continue;
+            }
+        };
+        let path = match (path.exists(), on_conflict) {
+            (false, _) | (true, ConflictPolicy::Overwrite) => path,
+            (true, ConflictPolicy::Rename) => free_path(&path),
+            (true, ConflictPolicy::Skip) => {
+                eprintln!(
+                    "Skipped {}: file exists (see --on-conflict)",
+                    path.display()
+                );
+                continue;
+            }
+        };
+
+        let lines = block.code.lines().count();
+        if dry_run {
+            eprintln!("Would write {} ({} lines)", path.display(), lines);
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, &block.code)
+            .map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+        eprintln!("Wrote {} ({} lines)", path.display(), lines);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &str = "Here you go:
+
+```rust
+// file: src/main.rs
+fn main() {}
+```
+
+```python
+print(\"hi\")
+```
+
+~~~toml Cargo.toml
+[package]
+~~~
+";
+
+    #[test]
+    fn it_should_parse_code_blocks_with_file_hints() {
+        let blocks = parse_code_blocks(RESPONSE);
+        assert_eq!(
+            blocks,
+            vec![
+                CodeBlock {
+                    language: "rust".to_string(),
+                    file_name: Some("src/main.rs".to_string()),
+                    code: "fn main() {}\n".to_string(),
+                },
+                CodeBlock {
+                    language: "python".to_string(),
+                    file_name: None,
+                    code: "print(\"hi\")\n".to_string(),
+                },
+                CodeBlock {
+                    language: "toml".to_string(),
+                    file_name: Some("Cargo.toml".to_string()),
+                    code: "[package]\n".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_infer_paths() {
+        let blocks = parse_code_blocks(RESPONSE);
+        assert_eq!(
+            relative_path(&blocks[1], 1),
+            Ok(PathBuf::from("snippet-2.py"))
+        );
+
+        let escaping = CodeBlock {
+            language: "sh".to_string(),
+            file_name: Some("../.bashrc".to_string()),
+            code: String::new(),
+        };
+        assert!(relative_path(&escaping, 0).is_err());
+        let absolute = CodeBlock {
+            file_name: Some("/etc/passwd".to_string()),
+            ..escaping
+        };
+        assert!(relative_path(&absolute, 0).is_err());
+    }
+
+    #[test]
+    fn it_should_handle_conflicts() {
+        let dir = std::env::temp_dir().join(format!("gemini-extract-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.rs"), "old").unwrap();
+
+        extract_code(RESPONSE, &dir, ConflictPolicy::Skip, false).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("src/main.rs")).unwrap(), "old");
+        assert!(dir.join("snippet-2.py").exists());
+
+        extract_code(RESPONSE, &dir, ConflictPolicy::Rename, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("src/main.1.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+
+        extract_code(RESPONSE, &dir, ConflictPolicy::Overwrite, true).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("src/main.rs")).unwrap(), "old");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
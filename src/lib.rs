@@ -3,9 +3,25 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+pub mod accumulator;
+pub mod auth;
+pub mod client;
+pub mod error;
+pub mod rate_limit;
+pub mod schema;
+pub mod sse;
+#[cfg(test)]
+mod test_server;
+
+pub use accumulator::ResponseAccumulator;
+pub use client::{ApiVersion, Backend, Client, ClientBuilder, StreamFormat};
+pub use error::Error;
+pub use rate_limit::{RateLimiter, RateLimiterMetrics};
+pub use schema::Schema;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CountTokensRequest {
-    pub contents: CandidateContent,
+    pub contents: Vec<RequestContent>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,14 +37,20 @@ pub struct GenerateContentRequest {
     pub generation_config: Option<GenerationConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tools>>,
+    /// Name of a cached content to use as context, e.g. `cachedContents/abc-123`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached_content: Option<String>,
+    /// Instructions given to the model apart from the conversation, e.g. a system prompt.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<RequestContent>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Tools {
     pub function_declarations: Option<Vec<FunctionDeclaration>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RequestContent {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub role: Option<String>,
@@ -41,19 +63,58 @@ pub struct CandidateContent {
     pub parts: Vec<Part>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct GenerationConfig {
-    pub max_output_tokens: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub top_k: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub stop_sequences: Option<Vec<String>>,
-    pub candidate_count: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidate_count: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    /// Fixed seed for (mostly) reproducible sampling.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i32>,
+    /// Return the log probabilities of the chosen tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_logprobs: Option<bool>,
+    /// Number of top candidate tokens to return log probabilities for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<i32>,
+    /// e.g. `TEXT`, `IMAGE` or `AUDIO`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_modalities: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_config: Option<ThinkingConfig>,
+    /// `application/json` for structured output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<Schema>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
+pub struct ThinkingConfig {
+    /// Tokens the model may spend thinking; `0` disables thinking.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_budget: Option<i32>,
+    /// Return thought summaries as [`Part::Thought`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_thoughts: Option<bool>,
+}
+
+#[derive(Debug, Clone)]
 pub enum Part {
     Text(String),
     InlineData {
@@ -66,8 +127,74 @@ pub enum Part {
     },
     FunctionCall {
         name: String,
-        args: HashMap<String, String>,
+        args: HashMap<String, serde_json::Value>,
+    },
+    FunctionResponse {
+        name: String,
+        response: serde_json::Value,
+    },
+    /// A text part flagged with `"thought": true`.
+    Thought(String),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Part", rename_all = "camelCase")]
+enum PartDef {
+    Text(String),
+    InlineData {
+        mime_type: String,
+        data: String,
+    },
+    FileData {
+        mime_type: String,
+        file_uri: String,
+    },
+    FunctionCall {
+        name: String,
+        #[serde(default)]
+        args: HashMap<String, serde_json::Value>,
     },
+    FunctionResponse {
+        name: String,
+        response: serde_json::Value,
+    },
+    // Handled by `Part`'s own implementations.
+    #[serde(skip)]
+    #[allow(dead_code)]
+    Thought(String),
+}
+
+impl Serialize for Part {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Part::Thought(text) => {
+                use serde::ser::SerializeMap;
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("text", text)?;
+                map.serialize_entry("thought", &true)?;
+                map.end()
+            }
+            part => PartDef::serialize(part, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Part {
+    /// Parts are single-key objects, except for metadata the API may add next
+    /// to the key (e.g. `thought`), which is stripped before matching.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut fields = serde_json::Map::deserialize(deserializer)?;
+        let thought = fields.remove("thought") == Some(serde_json::Value::Bool(true));
+        fields.remove("thoughtSignature");
+        fields.remove("videoMetadata");
+
+        let part = PartDef::deserialize(serde_json::Value::Object(fields))
+            .map_err(serde::de::Error::custom)?;
+        Ok(match part {
+            Part::Text(text) if thought => Part::Thought(text),
+            part => part,
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -84,28 +211,94 @@ pub struct GenerateContentResponseChunk {
     pub usage_metadata: Option<UsageMetadata>,
 }
 
+impl GenerateContentResponseChunk {
+    /// Concatenated text parts of all candidates.
+    pub fn text(&self) -> String {
+        self.parts()
+            .filter_map(|part| match part {
+                Part::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Concatenated thought summaries of all candidates.
+    pub fn thoughts(&self) -> String {
+        self.parts()
+            .filter_map(|part| match part {
+                Part::Thought(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn parts(&self) -> impl Iterator<Item = &Part> {
+        self.candidates
+            .iter()
+            .filter_map(|candidate| candidate.content.as_ref())
+            .flat_map(|content| content.parts.iter())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Candidate {
+    /// Position of the candidate when several are requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<u32>,
     pub content: Option<CandidateContent>,
     pub citation_metadata: Option<CitationMetadata>,
     pub safety_ratings: Option<Vec<SafetyRating>>,
     pub finish_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avg_logprobs: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs_result: Option<LogprobsResult>,
+}
+
+/// Returned when `response_logprobs` is set.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogprobsResult {
+    #[serde(default)]
+    pub top_candidates: Vec<TopCandidates>,
+    #[serde(default)]
+    pub chosen_candidates: Vec<LogprobsCandidate>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct TopCandidates {
+    #[serde(default)]
+    pub candidates: Vec<LogprobsCandidate>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogprobsCandidate {
+    pub token: String,
+    pub token_id: Option<i32>,
+    pub log_probability: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SafetyRating {
     pub category: String,
     pub probability: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A source of a segment of the candidate's text, as byte offsets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Citation {
-    end_index: u32,
-    license: String,
-    start_index: u32,
-    uri: String,
+    // Omitted by the API when zero.
+    #[serde(default)]
+    pub start_index: u32,
+    #[serde(default)]
+    pub end_index: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -114,12 +307,14 @@ pub struct CitationMetadata {
     pub citation_sources: Vec<Citation>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UsageMetadata {
-    candidates_token_count: Option<i32>,
-    prompt_token_count: i32,
-    total_token_count: i32,
+    pub candidates_token_count: Option<i32>,
+    pub prompt_token_count: i32,
+    pub total_token_count: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thoughts_token_count: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -138,11 +333,184 @@ pub struct FunctionParameters {
     pub required: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FunctionParametersProperty {
     pub r#type: String,
     pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r#enum: Option<Vec<String>>,
+    /// Schema of the elements of `ARRAY` parameters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Box<Schema>>,
+    /// Fields of `OBJECT` parameters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub properties: Option<HashMap<String, Schema>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbedContentRequest {
+    /// Required in batch requests, e.g. `models/text-embedding-004`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub content: RequestContent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_dimensionality: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbedContentResponse {
+    pub embedding: ContentEmbedding,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchEmbedContentsRequest {
+    pub requests: Vec<EmbedContentRequest>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchEmbedContentsResponse {
+    pub embeddings: Vec<ContentEmbedding>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentEmbedding {
+    pub values: Vec<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Model {
+    pub name: String,
+    pub base_model_id: Option<String>,
+    pub version: Option<String>,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub input_token_limit: Option<i32>,
+    pub output_token_limit: Option<i32>,
+    #[serde(default)]
+    pub supported_generation_methods: Vec<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<i32>,
+}
+
+impl Model {
+    /// Model name without the `models/` prefix, as used in request URLs.
+    pub fn id(&self) -> &str {
+        self.name.strip_prefix("models/").unwrap_or(&self.name)
+    }
+
+    pub fn supports(&self, method: &str) -> bool {
+        self.supported_generation_methods
+            .iter()
+            .any(|supported| supported == method)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListModelsResponse {
+    #[serde(default)]
+    pub models: Vec<Model>,
+    pub next_page_token: Option<String>,
+}
+
+/// Context cached on the server to be reused across requests.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<RequestContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contents: Option<Vec<RequestContent>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tools>>,
+    /// Time to live, e.g. `3600s`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expire_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub create_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_metadata: Option<CachedContentUsageMetadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContentUsageMetadata {
+    pub total_token_count: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListCachedContentsResponse {
+    #[serde(default)]
+    pub cached_contents: Vec<CachedContent>,
+    pub next_page_token: Option<String>,
+}
+
+/// File uploaded through the Files API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct File {
+    pub name: String,
+    pub display_name: Option<String>,
+    pub mime_type: Option<String>,
+    pub size_bytes: Option<String>,
+    pub create_time: Option<String>,
+    pub update_time: Option<String>,
+    pub expiration_time: Option<String>,
+    pub sha256_hash: Option<String>,
+    pub uri: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<FileError>,
+}
+
+/// Reason why processing an uploaded file failed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileError {
+    pub code: Option<i32>,
+    pub message: Option<String>,
+}
+
+impl File {
+    pub fn is_active(&self) -> bool {
+        self.state.as_deref() == Some("ACTIVE")
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.state.as_deref() == Some("FAILED")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateFileResponse {
+    pub file: File,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListFilesResponse {
+    #[serde(default)]
+    pub files: Vec<File>,
+    pub next_page_token: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
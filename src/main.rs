@@ -1,90 +1,364 @@
+mod cli;
+mod commands;
+mod config;
+mod extract;
+mod markdown;
+mod mcp;
+mod patch;
+mod rag;
+mod stats;
+mod template;
+mod terminal;
+mod tools;
+
 use atty::Stream;
 use chrono::prelude::*;
-use futures_util::stream::TryStreamExt;
+use clap::Parser;
+use cli::{Cli, Command};
+use config::Config;
+use futures_util::{
+    future,
+    stream::{self, StreamExt, TryStreamExt},
+};
 use gemini::{
-    GenerateContentRequest, GenerateContentResponse, GenerateContentResponseChunk,
-    GenerateContentResponseError, Part, RequestContent,
+    auth::{ServiceAccountKey, TokenSource},
+    schema::parse_json,
+    Backend, Client, GenerateContentRequest, GenerateContentResponse, GenerateContentResponseChunk,
+    GenerateContentResponseError, Part, RateLimiter, RequestContent, ResponseAccumulator, Schema,
 };
-use reqwest::Client;
-use reqwest_streams::*;
+use markdown::MarkdownRenderer;
+use mcp::McpServer;
 use serde_json::{json, Value};
 use slog::{debug, slog_o, Drain};
+use stats::Stats;
 use std::{
+    collections::HashMap,
     env,
-    fs::File,
+    fs::{self, File},
     io::{self, Error, Read, Write},
+    path::Path,
+    sync::Arc,
+    time::Duration,
 };
+use terminal::Spinner;
+use tokio::{signal, sync::watch};
+use tools::Toolbox;
+
+/// Rounds of tool calls after which --tools gives up on a final answer.
+const MAX_TOOL_TURNS: usize = 20;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref())?;
     let logger = init_logging();
+    let templates_dir = config.templates_dir();
+    let allowed_commands = config.allowed_commands.clone();
+    let mcp_servers = config.mcp_servers.clone();
 
-    let client = Client::new();
-    let api_key = env::var("API_KEY").expect("Usage: API_KEY=... cargo run");
-    let model = env::var("MODEL").unwrap_or("gemini-pro".to_string());
-    let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent",
-        model
-    );
-    let prompt = read_stdin_or_arg("Write a story about a magic backpack.".to_string());
+    let client = build_client(&cli, config)?;
+    match cli.command {
+        Some(Command::Models(command)) => return commands::models::run(&client, command).await,
+        Some(Command::Embed(args)) => return commands::embed::run(&client, args).await,
+        Some(Command::Index(args)) => return commands::index::run(&client, &cli.index, args).await,
+        Some(Command::Files(command)) => return commands::files::run(&client, command).await,
+        Some(Command::Cache(command)) => {
+            return commands::cache::run(&client, &cli.model, command).await
+        }
+        Some(Command::Batch(args)) => {
+            let config = cli.sampling.generation_config();
+            return commands::batch::run(&client, &cli.model, config, cli.no_stream, args).await;
+        }
+        Some(Command::Serve(args)) => return commands::serve::run(client, cli.model, args).await,
+        None => {}
+    }
+
+    let model = cli.model.clone();
+    let texts = match &cli.template {
+        Some(name) => {
+            let path = template::find(name, &templates_dir)?;
+            let text = fs::read_to_string(&path)
+                .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+            let vars = cli.vars.iter().cloned().collect();
+            vec![template::render(&text, &vars, || {
+                io::read_to_string(io::stdin())
+            })?]
+        }
+        None => compose_prompt(
+            "Write a story about a magic backpack.",
+            &cli.prompt,
+            read_piped_stdin(),
+        ),
+    };
+    let prompt = texts.join("\n\n");
+
+    let mut parts = Vec::new();
+    if cli.rag {
+        parts.extend(rag::retrieve_parts(&client, &cli.index, &prompt, cli.rag_top_k).await?);
+    }
+    for path in &cli.attach {
+        parts.push(commands::files::attachment_part(&client, path).await?);
+    }
+    for path in &cli.edit {
+        let path = patch::relative_path(path)?;
+        let contents = fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        parts.push(Part::Text(format!(
+            "File: {}\n```\n{}\n```",
+            path.display(),
+            contents.trim_end_matches('\n')
+        )));
+    }
+    if !cli.edit.is_empty() {
+        parts.push(Part::Text(patch::INSTRUCTION.to_string()));
+    }
+    parts.extend(texts.into_iter().map(Part::Text));
 
-    let request: GenerateContentRequest = GenerateContentRequest {
+    let response_schema = cli.json_schema.as_deref().map(load_schema).transpose()?;
+    let mut generation_config = cli.sampling.generation_config();
+    if let Some(schema) = &response_schema {
+        generation_config.response_mime_type = Some("application/json".to_string());
+        generation_config.response_schema = Some(schema.clone());
+    }
+
+    let mut toolbox = None;
+    if cli.tools || !cli.mcp.is_empty() {
+        let mut tools =
+            Toolbox::new(&cli.workdir, allowed_commands, terminal::confirm)?.builtin(cli.tools);
+        for name in &cli.mcp {
+            let config = mcp_servers
+                .get(name)
+                .ok_or_else(|| format!("No MCP server named {} in the config", name))?;
+            tools.add_server(McpServer::start(name, config).await?)?;
+        }
+        toolbox = Some(tools);
+    }
+
+    let mut request: GenerateContentRequest = GenerateContentRequest {
         contents: vec![RequestContent {
-            role: None,
-            parts: vec![Part::Text(prompt)],
+            role: Some("user".to_string()),
+            parts,
         }],
-        generation_config: None,
-        tools: None,
+        generation_config: Some(generation_config),
+        tools: toolbox.as_ref().map(|tools| vec![tools.declarations()]),
+        cached_content: cli.cache.as_deref().map(commands::cache::resource_name),
+        system_instruction: None,
     };
 
-    debug!(logger, "Requesting..."; "model" => format!("{}", model));
-    let input = json!(request);
-    let res = client
-        .post(url)
-        .query(&[("key", &api_key)])
-        .json(&input)
-        .send()
-        .await?;
-
-    debug!(logger, "Processing...");
-    let mut stream = res.json_array_stream::<serde_json::Value>(1024 * 1024);
+    commands::models::validate(&client, &model).await?;
 
     let mut output: Vec<serde_json::Value> = Vec::new();
-    while let Ok(Some(item)) = stream.try_next().await {
-        output.push(item.clone());
-        match parse_chunk(&item) {
-            Ok(chunk) => {
-                let text = chunk
-                    .candidates
-                    .iter()
-                    .filter_map(|candidate| match &candidate.content {
-                        Some(content) => Some(content),
-                        _ => None,
-                    })
-                    .flat_map(|content| {
-                        content.parts.iter().map(|part| match part {
-                            Part::Text(text) => Some(text.clone()),
-                            _ => None,
-                        })
-                    })
-                    .flatten()
-                    .collect::<String>();
-                print!("{}", text);
+    let mut response_text = String::new();
+    let render_markdown =
+        atty::is(Stream::Stdout) && !cli.raw && response_schema.is_none() && cli.edit.is_empty();
+    let mut renderer = render_markdown.then(MarkdownRenderer::new);
+    let mut ctrl_c = watch_ctrl_c();
+    let mut cancelled = false;
+    let mut stats = Stats::start();
+    for turn in 0.. {
+        debug!(logger, "Requesting..."; "model" => format!("{}", model), "turn" => turn, "stream" => !cli.no_stream);
+        let mut spinner = Spinner::start("Waiting for the response...");
+        let response = async {
+            if cli.no_stream {
+                let response = client.generate_content(&model, &request).await?;
+                Ok(stream::once(future::ready(Ok(response))).boxed())
+            } else {
+                client.stream_generate_content(&model, &request).await
             }
-            Err(err) => {
-                println!();
-                println!("Error: {:?}", err.error);
+        };
+        let mut stream = tokio::select! {
+            stream = response => stream?,
+            _ = ctrl_c.wait_for(|&pressed| pressed) => {
+                cancelled = true;
+                break;
+            }
+        };
+
+        debug!(logger, "Processing...");
+
+        let mut accumulator = ResponseAccumulator::new();
+        let mut failed = false;
+        loop {
+            let next = tokio::select! {
+                next = stream.try_next() => next,
+                _ = ctrl_c.wait_for(|&pressed| pressed) => {
+                    cancelled = true;
+                    break;
+                }
+            };
+            spinner.stop();
+            let item = match next {
+                Ok(Some(item)) => {
+                    stats.chunk();
+                    item
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    print!(
+                        "{}",
+                        renderer.as_mut().map(|r| r.finish()).unwrap_or_default()
+                    );
+                    println!();
+                    println!("Error: {}", err);
+                    failed = true;
+                    break;
+                }
+            };
+            output.push(item.clone());
+            match parse_chunk(&item) {
+                Ok(chunk) => {
+                    eprint!("{}", chunk.thoughts());
+                    let text = chunk.text();
+                    match renderer.as_mut() {
+                        Some(renderer) => print!("{}", renderer.push(&text)),
+                        None => print!("{}", text),
+                    }
+                    io::stdout().flush()?;
+                    response_text.push_str(&text);
+                    accumulator.push(chunk);
+                }
+                Err(err) => {
+                    print!(
+                        "{}",
+                        renderer.as_mut().map(|r| r.finish()).unwrap_or_default()
+                    );
+                    println!();
+                    println!("Error: {:?}", err.error);
+                    failed = true;
+                }
             }
         }
+
+        let response = accumulator.finish();
+        if let Some(usage) = &response.usage_metadata {
+            stats.add_usage(usage);
+        }
+        let Some(toolbox) = toolbox.as_mut().filter(|_| !failed && !cancelled) else {
+            break;
+        };
+        let mut model_parts = response
+            .candidates
+            .into_iter()
+            .next()
+            .and_then(|candidate| candidate.content)
+            .map(|content| content.parts)
+            .unwrap_or_default();
+        let calls: Vec<(String, HashMap<String, Value>)> = model_parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::FunctionCall { name, args } => Some((name.clone(), args.clone())),
+                _ => None,
+            })
+            .collect();
+        if calls.is_empty() {
+            break;
+        }
+        if turn == MAX_TOOL_TURNS {
+            println!();
+            println!(
+                "Error: stopped after {} rounds of tool calls",
+                MAX_TOOL_TURNS
+            );
+            break;
+        }
+
+        let mut responses = Vec::new();
+        for (name, args) in calls {
+            terminal::status(&format!("[tool] {} {}", name, json!(args)));
+            let response = toolbox.call(&name, &args).await;
+            responses.push(Part::FunctionResponse { name, response });
+        }
+        if *ctrl_c.borrow() {
+            cancelled = true;
+            break;
+        }
+        // Thoughts are only a summary for the user, not part of the conversation.
+        model_parts.retain(|part| !matches!(part, Part::Thought(_)));
+        request.contents.push(RequestContent {
+            role: Some("model".to_string()),
+            parts: model_parts,
+        });
+        request.contents.push(RequestContent {
+            role: Some("user".to_string()),
+            parts: responses,
+        });
+    }
+    stats.finish();
+    if let Some(renderer) = renderer.as_mut() {
+        print!("{}", renderer.finish());
+    }
+    if cancelled {
+        println!();
+        println!("Cancelled.");
+    }
+    if cli.stats {
+        terminal::status("");
+        terminal::status(&stats.summary());
     }
 
     debug!(logger, "Done.");
+    if let Some(limiter) = client.rate_limiter() {
+        let metrics = limiter.metrics();
+        debug!(logger, "Rate limiter";
+            "throttled" => metrics.throttled,
+            "wait_time" => format!("{:?}", metrics.wait_time));
+    }
 
-    write_log(model, &input, &output)?;
+    let input = json!(request);
+    let transcript = toolbox
+        .as_ref()
+        .map(Toolbox::transcript)
+        .unwrap_or_default();
+    write_log(model, &input, &output, transcript, &stats, cancelled)?;
+    if cancelled {
+        // Stops MCP servers, which `exit` would leave running.
+        drop(toolbox);
+        // The conventional status of a process interrupted by SIGINT.
+        std::process::exit(130);
+    }
+
+    if !cli.edit.is_empty() {
+        println!();
+        patch::apply_response(&response_text, cli.yes)?;
+    }
+
+    if let Some(dir) = &cli.extract_code {
+        println!();
+        extract::extract_code(&response_text, dir, cli.on_conflict, cli.dry_run)?;
+    }
+
+    if let Some(schema) = &response_schema {
+        println!();
+        parse_json::<Value>(&response_text, Some(schema)).map_err(|err| err.to_string())?;
+    }
 
     Ok(())
 }
 
+/// Becomes true on the first Ctrl-C, which cancels the generation; the second
+/// one exits right away, e.g. while waiting for a confirmation.
+fn watch_ctrl_c() -> watch::Receiver<bool> {
+    let (sender, receiver) = watch::channel(false);
+    tokio::spawn(async move {
+        if signal::ctrl_c().await.is_ok() {
+            sender.send_replace(true);
+            if signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+        // Keeps the sender, so that receivers wait instead of failing.
+        future::pending::<()>().await;
+    });
+    receiver
+}
+
+fn load_schema(path: &Path) -> Result<Schema, Box<dyn std::error::Error>> {
+    let json = fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    serde_json::from_str(&json)
+        .map_err(|err| format!("Invalid schema {}: {}", path.display(), err).into())
+}
+
 fn init_logging() -> slog::Logger {
     let decorator = slog_term::TermDecorator::new().build();
     let drain = slog_term::FullFormat::new(decorator).build().fuse();
@@ -93,21 +367,100 @@ fn init_logging() -> slog::Logger {
     slog::Logger::root(drain, slog_o!())
 }
 
-fn read_stdin_or_arg(default: String) -> String {
-    let mut input = String::new();
+fn build_client(cli: &Cli, config: Config) -> Result<Client, Box<dyn std::error::Error>> {
+    let mut builder = Client::builder(backend_from_env()?);
+    if let Some(base_url) = cli.base_url.clone().or(config.base_url) {
+        builder = builder.base_url(base_url);
+    }
+    if let Some(api_version) = cli.api_version.or(config.api_version) {
+        builder = builder.api_version(api_version);
+    }
+    if let Some(format) = cli.stream_format.or(config.stream_format) {
+        builder = builder.stream_format(format);
+    }
+    if let Some(secs) = cli.connect_timeout.or(config.connect_timeout) {
+        builder = builder.connect_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = cli.read_timeout.or(config.read_timeout) {
+        builder = builder.read_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = cli.timeout.or(config.timeout) {
+        builder = builder.timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = cli.idle_timeout.or(config.idle_timeout) {
+        builder = builder.idle_timeout(Duration::from_secs(secs));
+    }
+    if let Some(proxy) = cli.proxy.clone().or(config.proxy) {
+        builder = builder.proxy(proxy);
+    }
+    if let Some(ca_bundle) = cli.ca_bundle.clone().or(config.ca_bundle) {
+        builder = builder.ca_bundle(ca_bundle);
+    }
+    let rpm = cli.rpm.or(config.rpm);
+    let tpm = cli.tpm.or(config.tpm);
+    if rpm.is_some() || tpm.is_some() {
+        builder = builder
+            .rate_limiter(Arc::new(RateLimiter::new(rpm, tpm)))
+            .count_prompt_tokens(cli.count_tokens);
+    }
+
+    Ok(builder.build()?)
+}
 
-    if !atty::is(Stream::Stdin) {
-        io::stdin()
-            .read_to_string(&mut input)
-            .expect("Failed to read input");
-        return input.trim().to_string();
+fn backend_from_env() -> Result<Backend, Box<dyn std::error::Error>> {
+    if let Ok(api_key) = env::var("API_KEY") {
+        return Ok(Backend::GenerativeLanguage { api_key });
     }
 
-    let args: Vec<String> = env::args().skip(1).collect();
-    match args.len() {
-        0 => default,
-        1 => args.get(0).unwrap().clone(),
-        _ => panic!("Please provide at most one argument containing the prompt."),
+    let Ok(credentials) = env::var("GOOGLE_APPLICATION_CREDENTIALS") else {
+        panic!(
+            "Usage: API_KEY=... cargo run (or GOOGLE_APPLICATION_CREDENTIALS=... for Vertex AI)"
+        );
+    };
+    let key = ServiceAccountKey::from_file(credentials)?;
+    let project = env::var("VERTEX_PROJECT")
+        .ok()
+        .or(key.project_id.clone())
+        .expect("VERTEX_PROJECT must be set if the service account key has no project_id.");
+    let region = env::var("VERTEX_REGION").unwrap_or("us-central1".to_string());
+
+    Ok(Backend::VertexAi {
+        project,
+        region,
+        token_source: Box::new(TokenSource::new(key)),
+    })
+}
+
+/// Reads stdin if it is piped rather than a terminal.
+fn read_piped_stdin() -> Option<String> {
+    if atty::is(Stream::Stdin) {
+        return None;
+    }
+
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .expect("Failed to read input");
+    Some(input)
+}
+
+/// Texts of the prompt: the arguments as the instruction, followed by the
+/// piped input as the content it applies to (e.g. `git diff | gemini "Review this"`).
+fn compose_prompt(default: &str, args: &[String], stdin: Option<String>) -> Vec<String> {
+    let instruction = args.join(" ");
+    let texts: Vec<String> = [
+        instruction.trim(),
+        stdin.as_deref().unwrap_or_default().trim(),
+    ]
+    .into_iter()
+    .filter(|text| !text.is_empty())
+    .map(str::to_string)
+    .collect();
+
+    if texts.is_empty() {
+        vec![default.to_string()]
+    } else {
+        texts
     }
 }
 
@@ -137,20 +490,18 @@ fn parse_chunk(
 fn write_log(
     model: String,
     input: &serde_json::Value,
-    output: &Vec<serde_json::Value>,
+    output: &[serde_json::Value],
+    tool_calls: &[serde_json::Value],
+    stats: &Stats,
+    cancelled: bool,
 ) -> Result<(), Error> {
     let filename = format!(
         "log/{}_{}.json",
         Local::now().format("%Y-%m-%d_%H-%M-%S"),
         model
     );
-    let json = serde_json::to_string_pretty(&json!({
-        "meta": {
-            "model": model
-        },
-        "request": &input,
-        "response": &output
-    }))?;
+    let log = log_json(&model, input, output, tool_calls, stats, cancelled);
+    let json = serde_json::to_string_pretty(&log)?;
 
     let mut file = File::create(filename)?;
     file.write_all(json.as_bytes())?;
@@ -158,6 +509,32 @@ fn write_log(
     Ok(())
 }
 
+/// The log of a generation; a cancelled one only has the chunks received
+/// before Ctrl-C.
+fn log_json(
+    model: &str,
+    input: &serde_json::Value,
+    output: &[serde_json::Value],
+    tool_calls: &[serde_json::Value],
+    stats: &Stats,
+    cancelled: bool,
+) -> serde_json::Value {
+    let mut meta = stats.to_json();
+    meta["model"] = json!(model);
+    if cancelled {
+        meta["cancelled"] = json!(true);
+    }
+    let mut log = json!({
+        "meta": meta,
+        "request": input,
+        "response": output
+    });
+    if !tool_calls.is_empty() {
+        log["toolCalls"] = json!(tool_calls);
+    }
+    log
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -207,6 +584,165 @@ mod tests {
         let _chunk: GenerateContentResponseChunk = serde_json::from_value(data).unwrap();
     }
 
+    #[test]
+    fn it_should_compose_prompt_from_args_and_stdin() {
+        let args = vec!["Review".to_string(), "this diff".to_string()];
+        let diff = Some("+ added line\n".to_string());
+        assert_eq!(
+            compose_prompt("default", &args, diff.clone()),
+            vec!["Review this diff".to_string(), "+ added line".to_string()]
+        );
+        assert_eq!(
+            compose_prompt("default", &[], diff),
+            vec!["+ added line".to_string()]
+        );
+        assert_eq!(
+            compose_prompt("default", &args, Some("\n".to_string())),
+            vec!["Review this diff".to_string()]
+        );
+        assert_eq!(
+            compose_prompt("default", &[], None),
+            vec!["default".to_string()]
+        );
+    }
+
+    #[test]
+    fn it_should_separate_thoughts_from_text() {
+        let data: serde_json::Value = serde_json::from_str(EXAMPLE_CHUNK_WITH_THOUGHTS).unwrap();
+        let chunk: GenerateContentResponseChunk = serde_json::from_value(data).unwrap();
+        assert_eq!(chunk.thoughts(), "Counting in French.");
+        assert_eq!(chunk.text(), "un, deux, trois");
+        let logprobs = chunk.candidates[0].logprobs_result.as_ref().unwrap();
+        assert_eq!(logprobs.chosen_candidates[0].token, "un");
+    }
+
+    #[test]
+    fn it_should_keep_thought_flag_when_serialized() {
+        let parts = vec![
+            Part::Thought("Counting in French.".to_string()),
+            Part::Text("un, deux, trois".to_string()),
+        ];
+
+        let json = serde_json::to_value(&parts).unwrap();
+
+        assert_eq!(
+            json,
+            json!([
+                {"text": "Counting in French.", "thought": true},
+                {"text": "un, deux, trois"}
+            ])
+        );
+        let parsed: Vec<Part> = serde_json::from_value(json).unwrap();
+        assert!(matches!(&parsed[0], Part::Thought(text) if text == "Counting in French."));
+        assert!(matches!(&parsed[1], Part::Text(_)));
+    }
+
+    #[test]
+    fn it_should_build_generation_config_from_args() {
+        let cli = Cli::parse_from([
+            "gemini",
+            "--seed",
+            "42",
+            "--temperature",
+            "0",
+            "--stop",
+            "END",
+            "--presence-penalty",
+            "-0.5",
+            "--response-modalities",
+            "TEXT,IMAGE",
+            "--thinking-budget",
+            "0",
+        ]);
+        assert_eq!(
+            json!(cli.sampling.generation_config()),
+            json!({
+                "seed": 42,
+                "temperature": 0.0,
+                "stopSequences": ["END"],
+                "presencePenalty": -0.5,
+                "responseModalities": ["TEXT", "IMAGE"],
+                "thinkingConfig": {"thinkingBudget": 0}
+            })
+        );
+    }
+
+    #[test]
+    fn it_should_log_partial_response_of_cancelled_generation() {
+        let input = json!({"contents": [{"role": "user", "parts": [{"text": "Count"}]}]});
+        let output = vec![
+            json!({"candidates": [{"content": {"role": "model", "parts": [{"text": "un"}]}}]}),
+        ];
+        let mut stats = Stats::start();
+        stats.chunk();
+        stats.finish();
+
+        let log = log_json("gemini-pro", &input, &output, &[], &stats, true);
+
+        assert_eq!(log["meta"]["model"], "gemini-pro");
+        assert_eq!(log["meta"]["cancelled"], true);
+        assert_eq!(log["request"], input);
+        assert_eq!(log["response"], json!(output));
+        assert!(log.get("toolCalls").is_none());
+
+        let log = log_json("gemini-pro", &input, &output, &[], &stats, false);
+        assert!(log["meta"].get("cancelled").is_none());
+    }
+
+    #[test]
+    fn it_should_read_prompt_after_edited_files() {
+        let cli = Cli::try_parse_from([
+            "gemini",
+            "--edit",
+            "src/lib.rs",
+            "--edit",
+            "src/main.rs",
+            "rename",
+            "foo",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.edit,
+            vec![
+                std::path::PathBuf::from("src/lib.rs"),
+                std::path::PathBuf::from("src/main.rs")
+            ]
+        );
+        assert_eq!(cli.prompt, vec!["rename", "foo"]);
+    }
+
+    #[test]
+    fn it_should_reject_prompt_arguments_with_template() {
+        assert!(Cli::try_parse_from(["gemini", "--template", "review", "be", "brief"]).is_err());
+        assert!(Cli::try_parse_from(["gemini", "--template", "review"]).is_ok());
+    }
+
+    const EXAMPLE_CHUNK_WITH_THOUGHTS: &str = r#"{
+        "candidates": [
+          {
+            "content": {
+              "parts": [
+                {"text": "Counting in French.", "thought": true},
+                {"text": "un, deux, trois", "thoughtSignature": "c2ln"}
+              ],
+              "role": "model"
+            },
+            "finishReason": "STOP",
+            "avgLogprobs": -0.12,
+            "logprobsResult": {
+              "topCandidates": [{"candidates": [{"token": "un", "logProbability": -0.01}]}],
+              "chosenCandidates": [{"token": "un", "logProbability": -0.01}]
+            }
+          }
+        ],
+        "usageMetadata": {
+          "promptTokenCount": 5,
+          "candidatesTokenCount": 6,
+          "thoughtsTokenCount": 4,
+          "totalTokenCount": 15
+        }
+      }"#;
+
     const EXAMPLE_ERROR: &str = r#"[{
         "error": {
           "code": 503,
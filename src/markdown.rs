@@ -0,0 +1,399 @@
+//! Incremental rendering of streamed Markdown for the terminal.
+
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::as_24_bit_terminal_escaped,
+};
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+const UNDERLINE: &str = "\x1b[4m";
+const CYAN: &str = "\x1b[36m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+const THEME: &str = "base16-ocean.dark";
+
+/// Renders Markdown line by line as chunks arrive: text is held back until its
+/// line is complete, and tables until their last row, so that columns can be
+/// aligned.
+#[derive(Default)]
+pub struct MarkdownRenderer {
+    pending: String,
+    table: Vec<String>,
+    code: Option<CodeBlock>,
+    assets: Option<(SyntaxSet, Theme)>,
+}
+
+struct CodeBlock {
+    fence: String,
+    language: String,
+}
+
+impl MarkdownRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds streamed text, returning the rendering of the lines it completed.
+    pub fn push(&mut self, text: &str) -> String {
+        self.pending.push_str(text);
+        let mut output = String::new();
+        while let Some(end) = self.pending.find('\n') {
+            let line: String = self.pending.drain(..=end).collect();
+            output.push_str(&self.render_line(line.trim_end_matches(['\n', '\r'])));
+        }
+        output
+    }
+
+    /// Renders what is left at the end of the response.
+    pub fn finish(&mut self) -> String {
+        let mut output = String::new();
+        if !self.pending.is_empty() {
+            let line = std::mem::take(&mut self.pending);
+            output.push_str(&self.render_line(&line));
+        }
+        output.push_str(&self.flush_table());
+        self.code = None;
+        output
+    }
+
+    fn render_line(&mut self, line: &str) -> String {
+        let trimmed = line.trim_start();
+
+        if let Some(code) = &self.code {
+            if trimmed.starts_with(code.fence.as_str())
+                && trimmed.trim_matches(['`', '~']).is_empty()
+            {
+                self.code = None;
+                return format!("{}{}{}\n", DIM, line, RESET);
+            }
+            let language = code.language.clone();
+            return self.highlight(&language, line);
+        }
+
+        if trimmed.starts_with('|') {
+            self.table.push(trimmed.to_string());
+            return String::new();
+        }
+        let mut output = self.flush_table();
+
+        if let Some(fence) = ["```", "~~~"]
+            .into_iter()
+            .find(|fence| trimmed.starts_with(fence))
+        {
+            let marker_len = trimmed.len()
+                - trimmed
+                    .trim_start_matches(fence.chars().next().unwrap())
+                    .len();
+            self.code = Some(CodeBlock {
+                fence: trimmed[..marker_len].to_string(),
+                language: trimmed[marker_len..].trim().to_string(),
+            });
+            output.push_str(&format!("{}{}{}\n", DIM, line, RESET));
+            return output;
+        }
+
+        output.push_str(&render_block_line(line));
+        output.push('\n');
+        output
+    }
+
+    fn highlight(&mut self, language: &str, line: &str) -> String {
+        let (syntaxes, theme) = self.assets.get_or_insert_with(|| {
+            let mut themes = ThemeSet::load_defaults();
+            (
+                SyntaxSet::load_defaults_nonewlines(),
+                themes.themes.remove(THEME).unwrap_or_default(),
+            )
+        });
+        let syntax = syntaxes
+            .find_syntax_by_token(language)
+            .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+
+        // Each line is highlighted on its own, which loses multi-line state
+        // (e.g. block comments) but keeps the renderer free of self-references.
+        let mut highlighter = HighlightLines::new(syntax, theme);
+        match highlighter.highlight_line(line, syntaxes) {
+            Ok(ranges) => format!("{}{}\n", as_24_bit_terminal_escaped(&ranges, false), RESET),
+            Err(_) => format!("{}\n", line),
+        }
+    }
+
+    fn flush_table(&mut self) -> String {
+        if self.table.is_empty() {
+            return String::new();
+        }
+        let rows: Vec<Vec<String>> = std::mem::take(&mut self.table)
+            .iter()
+            .filter(|row| !is_separator_row(row))
+            .map(|row| {
+                split_row(row)
+                    .iter()
+                    .map(|cell| render_inline(cell))
+                    .collect()
+            })
+            .collect();
+
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|i| {
+                rows.iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|cell| visible_width(cell))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let mut output = String::new();
+        for (i, row) in rows.iter().enumerate() {
+            let cells: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(column, width)| {
+                    let cell = row.get(column).map(String::as_str).unwrap_or_default();
+                    let padding = " ".repeat(width - visible_width(cell));
+                    if i == 0 {
+                        format!("{}{}{}{}", BOLD, cell, RESET, padding)
+                    } else {
+                        format!("{}{}", cell, padding)
+                    }
+                })
+                .collect();
+            output.push_str(&format!("│ {} │\n", cells.join(" │ ")));
+            if i == 0 && rows.len() > 1 {
+                let rule: Vec<String> = widths.iter().map(|width| "─".repeat(*width)).collect();
+                output.push_str(&format!("├─{}─┤\n", rule.join("─┼─")));
+            }
+        }
+        output
+    }
+}
+
+/// Renders a line outside code blocks and tables.
+fn render_block_line(line: &str) -> String {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+        let style = if level == 1 { UNDERLINE } else { "" };
+        return format!(
+            "{}{}{}{}{}",
+            BOLD,
+            CYAN,
+            style,
+            render_inline(trimmed[level..].trim()),
+            RESET
+        );
+    }
+
+    let chars: String = trimmed.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.len() >= 3
+        && ["-", "*", "_"]
+            .iter()
+            .any(|c| chars == c.repeat(chars.len()))
+    {
+        return format!("{}{}{}", DIM, "─".repeat(40), RESET);
+    }
+
+    if let Some(quote) = trimmed.strip_prefix('>') {
+        return format!(
+            "{}{}│{} {}",
+            indent,
+            DIM,
+            RESET,
+            render_inline(quote.trim_start())
+        );
+    }
+
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = trimmed.strip_prefix(bullet) {
+            return format!("{}{}•{} {}", indent, YELLOW, RESET, render_inline(item));
+        }
+    }
+
+    let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 {
+        if let Some(item) = trimmed[digits..].strip_prefix(". ") {
+            return format!(
+                "{}{}{}.{} {}",
+                indent,
+                YELLOW,
+                &trimmed[..digits],
+                RESET,
+                render_inline(item)
+            );
+        }
+    }
+
+    format!("{}{}", indent, render_inline(trimmed))
+}
+
+/// Styles inline code, bold, italic and links.
+fn render_inline(text: &str) -> String {
+    let mut output = String::new();
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('`') {
+            if let Some(end) = after.find('`') {
+                output.push_str(&format!("{}{}{}", YELLOW, &after[..end], RESET));
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix("**").or_else(|| rest.strip_prefix("__")) {
+            let marker = &rest[..2];
+            if let Some(end) = after.find(marker).filter(|end| *end > 0) {
+                output.push_str(&format!(
+                    "{}{}{}",
+                    BOLD,
+                    render_inline(&after[..end]),
+                    RESET
+                ));
+                rest = &after[end + 2..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix('*') {
+            if let Some(end) = after
+                .find('*')
+                .filter(|end| *end > 0 && !after.starts_with(' '))
+            {
+                output.push_str(&format!(
+                    "{}{}{}",
+                    ITALIC,
+                    render_inline(&after[..end]),
+                    RESET
+                ));
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix('[') {
+            if let Some((label, url, len)) = parse_link(after) {
+                output.push_str(&format!(
+                    "{}{}{} {}({}){}",
+                    UNDERLINE, label, RESET, DIM, url, RESET
+                ));
+                rest = &after[len..];
+                continue;
+            }
+        }
+
+        let next = rest.chars().next().unwrap();
+        output.push(next);
+        rest = &rest[next.len_utf8()..];
+    }
+
+    output
+}
+
+/// Parses `label](url)` after an opening bracket, returning the length consumed.
+fn parse_link(text: &str) -> Option<(&str, &str, usize)> {
+    let label_end = text.find("](")?;
+    let url_end = text[label_end + 2..].find(')')? + label_end + 2;
+    Some((
+        &text[..label_end],
+        &text[label_end + 2..url_end],
+        url_end + 1,
+    ))
+}
+
+fn split_row(row: &str) -> Vec<String> {
+    let row = row.trim();
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let row = row.strip_suffix('|').unwrap_or(row);
+    row.split('|').map(|cell| cell.trim().to_string()).collect()
+}
+
+fn is_separator_row(row: &str) -> bool {
+    row.chars().all(|c| matches!(c, '|' | '-' | ':' | ' '))
+}
+
+/// Width of a string on screen, ignoring ANSI escape sequences.
+fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut in_escape = false;
+    for c in text.chars() {
+        match (in_escape, c) {
+            (false, '\x1b') => in_escape = true,
+            (true, 'm') => in_escape = false,
+            (true, _) => {}
+            (false, _) => width += 1,
+        }
+    }
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip_ansi(text: &str) -> String {
+        let mut output = String::new();
+        let mut in_escape = false;
+        for c in text.chars() {
+            match (in_escape, c) {
+                (false, '\x1b') => in_escape = true,
+                (true, 'm') => in_escape = false,
+                (true, _) => {}
+                (false, c) => output.push(c),
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn it_should_render_complete_lines_only() {
+        let mut renderer = MarkdownRenderer::new();
+        assert_eq!(renderer.push("# Tit"), "");
+        let heading = renderer.push("le\n- item with **bold");
+        assert_eq!(
+            heading,
+            format!("{}{}{}Title{}\n", BOLD, CYAN, UNDERLINE, RESET)
+        );
+        let item = renderer.push("** and `code`\n");
+        assert_eq!(strip_ansi(&item), "• item with bold and code\n");
+        assert!(item.contains(&format!("{}bold{}", BOLD, RESET)));
+        assert_eq!(strip_ansi(&renderer.finish()), "");
+    }
+
+    #[test]
+    fn it_should_align_tables() {
+        let mut renderer = MarkdownRenderer::new();
+        let output = renderer.push("| Name | Qty |\n|---|--:|\n| **Flour** | 250 |\n");
+        assert_eq!(output, "");
+        let output = renderer.push("\nAfter\n");
+        assert_eq!(
+            strip_ansi(&output),
+            "│ Name  │ Qty │\n├───────┼─────┤\n│ Flour │ 250 │\n\nAfter\n"
+        );
+    }
+
+    #[test]
+    fn it_should_highlight_code_blocks() {
+        let mut renderer = MarkdownRenderer::new();
+        let output = renderer.push("```rust\nfn main() {}\n```\n# Not a heading in code?\n");
+        let lines: Vec<String> = output.lines().map(strip_ansi).collect();
+        assert_eq!(
+            lines,
+            vec!["```rust", "fn main() {}", "```", "Not a heading in code?"]
+        );
+        // The code line is colored by the highlighter.
+        assert!(output.lines().nth(1).unwrap().contains("\x1b[38;2;"));
+    }
+
+    #[test]
+    fn it_should_flush_pending_text_on_finish() {
+        let mut renderer = MarkdownRenderer::new();
+        renderer.push("| a | b |\n");
+        assert_eq!(renderer.push("last *words*"), "");
+        assert_eq!(strip_ansi(&renderer.finish()), "│ a │ b │\nlast words\n");
+    }
+}
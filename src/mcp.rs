@@ -0,0 +1,411 @@
+//! Client for MCP (Model Context Protocol) servers run as child processes
+//! speaking JSON-RPC over stdio.
+
+use crate::config::McpServerConfig;
+use gemini::{FunctionDeclaration, FunctionParameters, FunctionParametersProperty, Schema};
+use serde_json::{json, Value};
+use std::{collections::HashMap, process::Stdio, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    process::{Child, ChildStdin, ChildStdout, Command},
+};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+struct McpTool {
+    name: String,
+    description: String,
+    input_schema: Value,
+    read_only: bool,
+}
+
+pub struct McpServer {
+    name: String,
+    trusted: bool,
+    // Killed when the server is dropped.
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    next_id: u64,
+    tools: Vec<McpTool>,
+}
+
+impl McpServer {
+    /// Launches the server, initializes the session and lists its tools.
+    pub async fn start(name: &str, config: &McpServerConfig) -> Result<Self, String> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| {
+                format!(
+                    "MCP server {}: cannot run {}: {}",
+                    name, config.command, err
+                )
+            })?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+
+        let mut server = Self {
+            name: name.to_string(),
+            trusted: config.trusted,
+            _child: child,
+            stdin,
+            stdout,
+            next_id: 1,
+            tools: Vec::new(),
+        };
+        server
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+            )
+            .await?;
+        server
+            .send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await?;
+        server.tools = server.list_tools().await?;
+        Ok(server)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn tool_names(&self) -> impl Iterator<Item = &str> {
+        self.tools.iter().map(|tool| tool.name.as_str())
+    }
+
+    pub fn has_tool(&self, name: &str) -> bool {
+        self.tools.iter().any(|tool| tool.name == name)
+    }
+
+    /// Whether the tool may be called without confirmation.
+    pub fn is_trusted(&self, name: &str) -> bool {
+        self.trusted
+            || self
+                .tools
+                .iter()
+                .any(|tool| tool.name == name && tool.read_only)
+    }
+
+    pub fn declarations(&self) -> Vec<FunctionDeclaration> {
+        self.tools
+            .iter()
+            .map(|tool| FunctionDeclaration {
+                name: tool.name.clone(),
+                description: tool.description.clone(),
+                parameters: function_parameters(&tool.input_schema),
+            })
+            .collect()
+    }
+
+    /// Calls a tool, returning its text content; results flagged as errors
+    /// are returned as `Err` for the model to see.
+    pub async fn call(
+        &mut self,
+        name: &str,
+        args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        let result = self
+            .request("tools/call", json!({ "name": name, "arguments": args }))
+            .await?;
+        let text = result["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|item| match item["type"].as_str() {
+                Some("text") => item["text"].as_str().unwrap_or_default().to_string(),
+                Some("resource") => match item["resource"]["text"].as_str() {
+                    Some(text) => text.to_string(),
+                    None => format!("[resource {}]", item["resource"]["uri"]),
+                },
+                kind => format!("[{} content]", kind.unwrap_or("unknown")),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if result["isError"] == true {
+            Err(text)
+        } else {
+            Ok(text)
+        }
+    }
+
+    async fn list_tools(&mut self) -> Result<Vec<McpTool>, String> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+            for tool in result["tools"].as_array().into_iter().flatten() {
+                tools.push(McpTool {
+                    name: tool["name"].as_str().unwrap_or_default().to_string(),
+                    description: tool["description"].as_str().unwrap_or_default().to_string(),
+                    input_schema: tool["inputSchema"].clone(),
+                    read_only: tool["annotations"]["readOnlyHint"] == true,
+                });
+            }
+            match result["nextCursor"].as_str() {
+                Some(next) => cursor = Some(next.to_string()),
+                None => return Ok(tools),
+            }
+        }
+    }
+
+    async fn request(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+            .await?;
+        tokio::time::timeout(REQUEST_TIMEOUT, self.response(id))
+            .await
+            .map_err(|_| self.error(format!("{} timed out", method)))?
+    }
+
+    /// Reads messages until the response to the request `id`.
+    async fn response(&mut self, id: u64) -> Result<Value, String> {
+        loop {
+            let line = self
+                .stdout
+                .next_line()
+                .await
+                .map_err(|err| self.error(err))?
+                .ok_or_else(|| self.error("exited"))?;
+            // Not JSON-RPC, e.g. a log line.
+            let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                continue;
+            };
+
+            if let Some(method) = message["method"].as_str() {
+                // Requests of the server need an answer, notifications don't.
+                if let Some(request_id) = message.get("id") {
+                    let reply = if method == "ping" {
+                        json!({ "jsonrpc": "2.0", "id": request_id, "result": {} })
+                    } else {
+                        json!({
+                            "jsonrpc": "2.0",
+                            "id": request_id,
+                            "error": { "code": -32601, "message": "Method not found" },
+                        })
+                    };
+                    self.send(reply).await?;
+                }
+                continue;
+            }
+            if message["id"] != id {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                return Err(self.error(error["message"].as_str().unwrap_or("unknown error")));
+            }
+            return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    async fn send(&mut self, message: Value) -> Result<(), String> {
+        let mut line = message.to_string();
+        line.push('\n');
+        self.stdin
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|err| self.error(err))?;
+        self.stdin.flush().await.map_err(|err| self.error(err))
+    }
+
+    fn error(&self, err: impl std::fmt::Display) -> String {
+        format!("MCP server {}: {}", self.name, err)
+    }
+}
+
+fn function_parameters(input_schema: &Value) -> FunctionParameters {
+    let schema = convert_schema(input_schema);
+    FunctionParameters {
+        r#type: "OBJECT".to_string(),
+        properties: schema
+            .properties
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, property)| {
+                (
+                    name,
+                    FunctionParametersProperty {
+                        r#type: property.r#type,
+                        description: property.description.unwrap_or_default(),
+                        r#enum: property.r#enum,
+                        items: property.items,
+                        properties: property.properties,
+                        required: property.required,
+                    },
+                )
+            })
+            .collect(),
+        required: schema.required.unwrap_or_default(),
+    }
+}
+
+/// Converts a JSON Schema to Gemini's OpenAPI subset, dropping what it cannot
+/// express, e.g. `{"type": ["string", "null"]}` → `{"type": "STRING", "nullable": true}`.
+fn convert_schema(schema: &Value) -> Schema {
+    if let Some(variants) = schema
+        .get("anyOf")
+        .or_else(|| schema.get("oneOf"))
+        .and_then(Value::as_array)
+    {
+        let mut converted = convert_schema(
+            variants
+                .iter()
+                .find(|variant| variant["type"] != "null")
+                .unwrap_or(&json!({})),
+        );
+        if variants.iter().any(|variant| variant["type"] == "null") {
+            converted.nullable = Some(true);
+        }
+        if let Some(description) = schema["description"].as_str() {
+            converted.description = Some(description.to_string());
+        }
+        return converted;
+    }
+
+    let (type_name, nullable) = match &schema["type"] {
+        Value::String(type_name) => (type_name.as_str(), false),
+        Value::Array(types) => (
+            types
+                .iter()
+                .filter_map(Value::as_str)
+                .find(|type_name| *type_name != "null")
+                .unwrap_or("string"),
+            types.iter().any(|type_name| type_name == "null"),
+        ),
+        _ if schema.get("properties").is_some() => ("object", false),
+        _ => ("string", false),
+    };
+
+    Schema {
+        r#type: type_name.to_uppercase(),
+        format: None,
+        description: schema["description"].as_str().map(str::to_string),
+        nullable: nullable.then_some(true),
+        // Gemini only supports enums of strings.
+        r#enum: schema["enum"]
+            .as_array()
+            .filter(|_| type_name == "string")
+            .map(|values| {
+                values
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            }),
+        properties: schema["properties"].as_object().map(|properties| {
+            properties
+                .iter()
+                .map(|(name, property)| (name.clone(), convert_schema(property)))
+                .collect()
+        }),
+        required: schema["required"].as_array().map(|required| {
+            required
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        }),
+        items: schema
+            .get("items")
+            .map(|items| Box::new(convert_schema(items))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers the requests of a session in the order the client sends them.
+    const MOCK_SERVER: &str = r#"
+read -r line
+echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"mock","version":"1.0"}}}'
+read -r line
+read -r line
+echo 'mock server ready'
+echo '{"jsonrpc":"2.0","method":"notifications/message","params":{"level":"info","data":"listing"}}'
+echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"echo","description":"Echoes a text","inputSchema":{"type":"object","properties":{"text":{"type":"string"},"tags":{"type":"array","items":{"type":"string"}}},"required":["text"]},"annotations":{"readOnlyHint":true}}],"nextCursor":"2"}}'
+read -r line
+echo '{"jsonrpc":"2.0","id":3,"result":{"tools":[{"name":"delete","inputSchema":{"type":"object"}}]}}'
+read -r line
+text=$(echo "$line" | sed 's/.*"text":"\([^"]*\)".*/\1/')
+echo "{\"jsonrpc\":\"2.0\",\"id\":4,\"result\":{\"content\":[{\"type\":\"text\",\"text\":\"$text\"}]}}"
+read -r line
+echo '{"jsonrpc":"2.0","id":5,"result":{"content":[{"type":"text","text":"not found"}],"isError":true}}'
+"#;
+
+    #[tokio::test]
+    async fn it_should_call_tools_of_stdio_server() {
+        let script = std::env::temp_dir().join(format!("gemini-mcp-{}.sh", std::process::id()));
+        std::fs::write(&script, MOCK_SERVER).unwrap();
+        let config = McpServerConfig {
+            command: "sh".to_string(),
+            args: vec![script.to_string_lossy().to_string()],
+            ..Default::default()
+        };
+
+        let mut server = McpServer::start("mock", &config).await.unwrap();
+
+        assert_eq!(server.tool_names().collect::<Vec<_>>(), ["echo", "delete"]);
+        assert!(server.is_trusted("echo"));
+        assert!(!server.is_trusted("delete"));
+        let declarations = json!(server.declarations());
+        assert_eq!(
+            declarations[0]["parameters"]["properties"]["tags"],
+            json!({"type": "ARRAY", "description": "", "items": {"type": "STRING"}})
+        );
+        assert_eq!(declarations[0]["parameters"]["required"], json!(["text"]));
+
+        let args = HashMap::from([("text".to_string(), json!("hello"))]);
+        assert_eq!(server.call("echo", &args).await, Ok("hello".to_string()));
+        assert_eq!(
+            server.call("delete", &HashMap::new()).await,
+            Err("not found".to_string())
+        );
+
+        std::fs::remove_file(&script).unwrap();
+    }
+
+    #[test]
+    fn it_should_convert_json_schema() {
+        let schema = convert_schema(&json!({
+            "type": "object",
+            "properties": {
+                "state": {"type": "string", "enum": ["open", "closed"]},
+                "assignee": {"anyOf": [{"type": "string"}, {"type": "null"}], "description": "Login"},
+                "limit": {"type": ["integer", "null"], "default": 10},
+            },
+        }));
+
+        assert_eq!(
+            json!(schema),
+            json!({
+                "type": "OBJECT",
+                "properties": {
+                    "state": {"type": "STRING", "enum": ["open", "closed"]},
+                    "assignee": {"type": "STRING", "description": "Login", "nullable": true},
+                    "limit": {"type": "INTEGER", "nullable": true},
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,434 @@
+//! Applies unified diffs proposed by the model to the working tree.
+
+use crate::{extract::check_relative, terminal::confirm};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Asks the model for a diff against the files included in the prompt.
+pub const INSTRUCTION: &str = "Propose the requested changes as a unified diff (as printed by \
+`git diff`) against the files above, with paths relative to the current directory. \
+Only output the diff.";
+
+#[derive(Debug, PartialEq)]
+pub struct FilePatch {
+    /// `None` for new files.
+    pub old_path: Option<PathBuf>,
+    /// `None` for deleted files.
+    pub new_path: Option<PathBuf>,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Hunk {
+    pub header: String,
+    /// 1-based line the hunk claims to start at in the old file.
+    pub old_start: usize,
+    /// Context and removed lines.
+    pub old_lines: Vec<String>,
+    /// Context and added lines.
+    pub new_lines: Vec<String>,
+}
+
+impl FilePatch {
+    fn path(&self) -> &Path {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap()
+    }
+}
+
+/// Parses the unified diffs in a response, ignoring surrounding text and code
+/// fences. Hunks are delimited by their line prefixes rather than the counts
+/// in their headers, which models often get wrong.
+pub fn parse_unified_diff(text: &str) -> Result<Vec<FilePatch>, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut patches: Vec<FilePatch> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if let (Some(old), Some(new)) = (
+            line.strip_prefix("--- "),
+            lines.get(i + 1).and_then(|next| next.strip_prefix("+++ ")),
+        ) {
+            let (old_path, new_path) = (diff_path(old)?, diff_path(new)?);
+            if old_path.is_none() && new_path.is_none() {
+                return Err("diff without a file path".to_string());
+            }
+            patches.push(FilePatch {
+                old_path,
+                new_path,
+                hunks: Vec::new(),
+            });
+            i += 2;
+            continue;
+        }
+
+        if line.starts_with("@@") {
+            let patch = patches
+                .last_mut()
+                .ok_or_else(|| format!("hunk without file header: {}", line))?;
+            let mut hunk = Hunk {
+                header: line.to_string(),
+                old_start: parse_old_start(line)?,
+                old_lines: Vec::new(),
+                new_lines: Vec::new(),
+            };
+            let mut trailing_blanks = 0;
+            i += 1;
+            while let Some(line) = lines.get(i) {
+                let starts_file = line.starts_with("--- ")
+                    && lines
+                        .get(i + 1)
+                        .is_some_and(|next| next.starts_with("+++ "));
+                if starts_file || line.starts_with("@@") {
+                    break;
+                }
+                trailing_blanks = if line.is_empty() {
+                    trailing_blanks + 1
+                } else {
+                    0
+                };
+                match line.chars().next() {
+                    Some('-') => hunk.old_lines.push(line[1..].to_string()),
+                    Some('+') => hunk.new_lines.push(line[1..].to_string()),
+                    Some(' ') => {
+                        hunk.old_lines.push(line[1..].to_string());
+                        hunk.new_lines.push(line[1..].to_string());
+                    }
+                    // Context lines whose trailing space got lost.
+                    None => {
+                        hunk.old_lines.push(String::new());
+                        hunk.new_lines.push(String::new());
+                    }
+                    Some('\\') => {}
+                    Some(_) => break,
+                }
+                i += 1;
+            }
+            // Trailing blank lines are more likely the end of the diff than context.
+            for _ in 0..trailing_blanks {
+                hunk.old_lines.pop();
+                hunk.new_lines.pop();
+            }
+            patch.hunks.push(hunk);
+            continue;
+        }
+
+        i += 1;
+    }
+
+    Ok(patches)
+}
+
+/// `a/src/main.rs` → `src/main.rs`, `/dev/null` → `None`.
+fn diff_path(header: &str) -> Result<Option<PathBuf>, String> {
+    let path = header.split('\t').next().unwrap_or_default().trim();
+    if path == "/dev/null" {
+        return Ok(None);
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    let path = PathBuf::from(path);
+    check_relative(&path)?;
+    Ok(Some(path))
+}
+
+/// Reads `l` from `@@ -l,s +l,s @@`.
+fn parse_old_start(header: &str) -> Result<usize, String> {
+    header
+        .split_whitespace()
+        .nth(1)
+        .and_then(|range| range.strip_prefix('-'))
+        .and_then(|range| range.split(',').next())
+        .and_then(|start| start.parse().ok())
+        .ok_or_else(|| format!("invalid hunk header: {}", header))
+}
+
+/// Applies the hunks that match, searching near the line each claims to start
+/// at, and returns the new content with the headers of the hunks that failed.
+///
+/// The file keeps its line endings (`\r\n` if it used them) and whether it
+/// ended with a newline; new files get one.
+pub fn apply_hunks(content: &str, hunks: &[Hunk]) -> (String, Vec<String>) {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let mut failed = Vec::new();
+    let mut offset: isize = 0;
+
+    for hunk in hunks {
+        let expected = (hunk.old_start as isize - 1 + offset).max(0) as usize;
+        match find_hunk(&lines, &hunk.old_lines, expected) {
+            Some(position) => {
+                lines.splice(
+                    position..position + hunk.old_lines.len(),
+                    hunk.new_lines.iter().cloned(),
+                );
+                offset += hunk.new_lines.len() as isize - hunk.old_lines.len() as isize;
+                offset += position as isize - expected as isize;
+            }
+            None => failed.push(hunk.header.clone()),
+        }
+    }
+
+    let newline = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut output = lines.join(newline);
+    if !lines.is_empty() && (content.is_empty() || content.ends_with('\n')) {
+        output.push_str(newline);
+    }
+    (output, failed)
+}
+
+/// Closest position to `expected` where the old lines match, ignoring trailing
+/// whitespace.
+fn find_hunk(lines: &[String], old_lines: &[String], expected: usize) -> Option<usize> {
+    if old_lines.is_empty() {
+        return Some(expected.min(lines.len()));
+    }
+    let last_start = lines.len().checked_sub(old_lines.len())?;
+    let matches_at = |start: usize| {
+        lines[start..start + old_lines.len()]
+            .iter()
+            .zip(old_lines)
+            .all(|(line, old)| line.trim_end() == old.trim_end())
+    };
+
+    (0..=last_start.max(expected))
+        .flat_map(|distance| {
+            [
+                expected.checked_sub(distance),
+                expected.checked_add(distance),
+            ]
+        })
+        .flatten()
+        .filter(|start| *start <= last_start)
+        .find(|start| matches_at(*start))
+}
+
+/// `path` relative to the current directory, as the diff should refer to it;
+/// files outside of it cannot be edited.
+pub fn relative_path(path: &Path) -> Result<PathBuf, String> {
+    let absolute = path
+        .canonicalize()
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    let current_dir = std::env::current_dir()
+        .and_then(|dir| dir.canonicalize())
+        .map_err(|err| format!("Failed to read the current directory: {}", err))?;
+    absolute
+        .strip_prefix(&current_dir)
+        .map(Path::to_path_buf)
+        .map_err(|_| {
+            format!(
+                "{}: only files in the current directory can be edited",
+                path.display()
+            )
+        })
+}
+
+/// Parses the diff of the response, shows what applies, and writes the
+/// changes after confirmation (unless `yes`).
+pub fn apply_response(text: &str, yes: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let patches = parse_unified_diff(text)?;
+    if patches.is_empty() {
+        return Err("The response contains no unified diff.".into());
+    }
+
+    let mut changes: Vec<(PathBuf, Option<String>)> = Vec::new();
+    let mut failures = 0;
+    for patch in &patches {
+        let path = patch.path().to_path_buf();
+        let old_content = match &patch.old_path {
+            Some(old_path) => match fs::read_to_string(old_path) {
+                Ok(content) => content,
+                Err(err) => {
+                    eprintln!("{}: cannot read ({}), skipped", old_path.display(), err);
+                    failures += patch.hunks.len();
+                    continue;
+                }
+            },
+            None => String::new(),
+        };
+
+        let (new_content, failed) = apply_hunks(&old_content, &patch.hunks);
+        let applied = patch.hunks.len() - failed.len();
+        eprintln!(
+            "{}: {} of {} hunks apply{}",
+            path.display(),
+            applied,
+            patch.hunks.len(),
+            match (&patch.old_path, &patch.new_path) {
+                (None, _) => " (new file)",
+                (_, None) => " (deleted)",
+                _ => "",
+            }
+        );
+        for header in &failed {
+            eprintln!("  failed: {}", header);
+        }
+        failures += failed.len();
+
+        if patch.new_path.is_none() && failed.is_empty() {
+            changes.push((path, None));
+        } else if applied > 0 {
+            changes.push((path, Some(new_content)));
+        }
+    }
+
+    if changes.is_empty() {
+        return Err("None of the hunks apply.".into());
+    }
+    if !yes && !confirm(&format!("Apply the changes to {} files?", changes.len())) {
+        eprintln!("Not applied.");
+        return Ok(());
+    }
+
+    for (path, content) in &changes {
+        match content {
+            Some(content) => {
+                if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(path, content)?;
+            }
+            None => fs::remove_file(path)?,
+        }
+    }
+    eprintln!("Applied changes to {} files.", changes.len());
+
+    if failures > 0 {
+        return Err(format!("{} hunks failed to apply.", failures).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &str = "Sure:
+
+```diff
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -2,3 +2,3 @@
+ fn one() {}
+-fn two() {}
++fn deux() {}
+ fn three() {}
+@@ -9,2 +9,3 @@
+ fn nine() {}
++fn nine_and_a_half() {}
+ fn ten() {}
+--- /dev/null
++++ b/NOTES.md
+@@ -0,0 +1 @@
++Renamed two.
+```
+";
+
+    #[test]
+    fn it_should_parse_unified_diff() {
+        let patches = parse_unified_diff(RESPONSE).unwrap();
+        assert_eq!(patches.len(), 2);
+        assert_eq!(patches[0].old_path, Some(PathBuf::from("src/lib.rs")));
+        assert_eq!(patches[0].hunks.len(), 2);
+        assert_eq!(
+            patches[0].hunks[0],
+            Hunk {
+                header: "@@ -2,3 +2,3 @@".to_string(),
+                old_start: 2,
+                old_lines: vec![
+                    "fn one() {}".to_string(),
+                    "fn two() {}".to_string(),
+                    "fn three() {}".to_string(),
+                ],
+                new_lines: vec![
+                    "fn one() {}".to_string(),
+                    "fn deux() {}".to_string(),
+                    "fn three() {}".to_string(),
+                ],
+            }
+        );
+        assert_eq!(patches[1].old_path, None);
+        assert_eq!(patches[1].new_path, Some(PathBuf::from("NOTES.md")));
+
+        assert!(parse_unified_diff("--- a/../x\n+++ b/../x\n").is_err());
+        assert_eq!(
+            parse_unified_diff("--- /dev/null\n+++ /dev/null\n@@ -0,0 +1 @@\n+x\n"),
+            Err("diff without a file path".to_string())
+        );
+    }
+
+    #[test]
+    fn it_should_apply_hunks_with_offset() {
+        // Two lines were added at the top since the model saw the file.
+        let content = "// a\n// b\nfn zero() {}\nfn one() {}\nfn two() {}\nfn three() {}\n\
+            fn four() {}\nfn five() {}\nfn six() {}\nfn seven() {}\nfn eight() {}\n\
+            fn nine() {}\nfn ten() {}\n";
+        let patches = parse_unified_diff(RESPONSE).unwrap();
+
+        let (output, failed) = apply_hunks(content, &patches[0].hunks);
+
+        assert!(failed.is_empty());
+        assert!(output.contains("fn one() {}\nfn deux() {}\nfn three() {}\n"));
+        assert!(output.contains("fn nine() {}\nfn nine_and_a_half() {}\nfn ten() {}\n"));
+        let (output, _) = apply_hunks("", &patches[1].hunks);
+        assert_eq!(output, "Renamed two.\n");
+    }
+
+    #[test]
+    fn it_should_report_failed_hunks() {
+        let patches = parse_unified_diff(RESPONSE).unwrap();
+        let content = "fn one() {}\nfn dos() {}\nfn three() {}\nfn nine() {}\nfn ten() {}\n";
+
+        let (output, failed) = apply_hunks(content, &patches[0].hunks);
+
+        assert_eq!(failed, vec!["@@ -2,3 +2,3 @@".to_string()]);
+        assert_eq!(
+            output,
+            "fn one() {}\nfn dos() {}\nfn three() {}\nfn nine() {}\nfn nine_and_a_half() {}\nfn ten() {}\n"
+        );
+    }
+
+    #[test]
+    fn it_should_keep_line_endings() {
+        let patches = parse_unified_diff(RESPONSE).unwrap();
+        let hunks = &patches[0].hunks[..1];
+
+        let (output, _) = apply_hunks(
+            "fn zero() {}\r\nfn one() {}\r\nfn two() {}\r\nfn three() {}\r\n",
+            hunks,
+        );
+        assert_eq!(
+            output,
+            "fn zero() {}\r\nfn one() {}\r\nfn deux() {}\r\nfn three() {}\r\n"
+        );
+
+        let (output, _) = apply_hunks(
+            "fn zero() {}\nfn one() {}\nfn two() {}\nfn three() {}",
+            hunks,
+        );
+        assert_eq!(
+            output,
+            "fn zero() {}\nfn one() {}\nfn deux() {}\nfn three() {}"
+        );
+    }
+
+    #[test]
+    fn it_should_make_edited_paths_relative() {
+        let current_dir = std::env::current_dir().unwrap();
+        assert_eq!(
+            relative_path(&current_dir.join("src/../src/patch.rs")),
+            Ok(PathBuf::from("src/patch.rs"))
+        );
+        assert!(relative_path(&current_dir.join("..")).is_err());
+    }
+}
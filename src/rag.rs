@@ -0,0 +1,197 @@
+use crate::commands::embed::embed_all;
+use gemini::{Client, Part};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Files larger than this are not indexed.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Chunk {
+    pub source: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    pub values: Vec<f32>,
+}
+
+/// Embedded chunks of a directory, stored as a JSON file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Index {
+    pub model: String,
+    pub chunks: Vec<Chunk>,
+}
+
+impl Index {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = fs::read_to_string(path).map_err(|err| {
+            format!(
+                "Failed to read index {} (create it with `gemini index`): {}",
+                path.display(),
+                err
+            )
+        })?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Returns the `k` chunks most similar to the query, best match first.
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(&Chunk, f32)> {
+        let mut scored: Vec<(&Chunk, f32)> = self
+            .chunks
+            .iter()
+            .map(|chunk| (chunk, cosine_similarity(query, &chunk.values)))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(k);
+        scored
+    }
+}
+
+/// Embeds the prompt and returns the best matching chunks as context parts,
+/// preceded by an instruction to cite them.
+pub async fn retrieve_parts(
+    client: &Client,
+    index_path: &Path,
+    prompt: &str,
+    top_k: usize,
+) -> Result<Vec<Part>, Box<dyn std::error::Error>> {
+    let index = Index::load(index_path)?;
+    let query = embed_all(
+        client,
+        &index.model,
+        &[prompt.to_string()],
+        Some("RETRIEVAL_QUERY"),
+        None,
+    )
+    .await?
+    .pop()
+    .ok_or("No embedding returned for the prompt.")?;
+
+    let mut parts = vec![Part::Text(
+        "Answer using the following sources where relevant, and cite them as [source:lines]."
+            .to_string(),
+    )];
+    for (chunk, _) in index.search(&query, top_k) {
+        parts.push(Part::Text(format!(
+            "[{}:{}-{}]\n{}",
+            chunk.source, chunk.start_line, chunk.end_line, chunk.text
+        )));
+    }
+
+    Ok(parts)
+}
+
+/// Recursively collects the files of a directory, skipping hidden entries.
+pub fn collect_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            files.extend(collect_files(&path)?);
+        } else if file_type.is_file() && entry.metadata()?.len() <= MAX_FILE_SIZE {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Splits text into chunks of whole lines of at most `max_chars` characters
+/// (unless a single line is longer), with 1-based inclusive line ranges.
+pub fn chunk_text(text: &str, max_chars: usize) -> Vec<(usize, usize, String)> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut start_line = 1;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        if !current.is_empty() && current.len() + line.len() + 1 > max_chars {
+            if !current.trim().is_empty() {
+                chunks.push((start_line, line_number - 1, current.clone()));
+            }
+            current.clear();
+        }
+        if current.is_empty() {
+            start_line = line_number;
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+
+    if !current.trim().is_empty() {
+        chunks.push((start_line, text.lines().count(), current));
+    }
+
+    chunks
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_chunk_by_lines() {
+        let text = "one\ntwo\nthree\nfour\n";
+        let chunks = chunk_text(text, 10);
+        assert_eq!(
+            chunks,
+            vec![
+                (1, 2, "one\ntwo\n".to_string()),
+                (3, 3, "three\n".to_string()),
+                (4, 4, "four\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_rank_chunks_by_similarity() {
+        let chunk = |source: &str, values: Vec<f32>| Chunk {
+            source: source.to_string(),
+            start_line: 1,
+            end_line: 1,
+            text: String::new(),
+            values,
+        };
+        let index = Index {
+            model: "text-embedding-004".to_string(),
+            chunks: vec![
+                chunk("a", vec![1.0, 0.0]),
+                chunk("b", vec![0.0, 1.0]),
+                chunk("c", vec![0.7, 0.7]),
+            ],
+        };
+
+        let results = index.search(&[0.0, 1.0], 2);
+
+        let sources: Vec<&str> = results.iter().map(|(c, _)| c.source.as_str()).collect();
+        assert_eq!(sources, vec!["b", "c"]);
+    }
+}
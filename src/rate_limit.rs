@@ -0,0 +1,185 @@
+//! Client-side throttling to stay within the API's per-minute quotas.
+
+use std::{sync::Mutex, time::Duration};
+use tokio::time::Instant;
+
+/// Token buckets for requests and tokens per minute, shared by all clones of a
+/// [`Client`](crate::Client) (or several clients, through an `Arc`).
+///
+/// Each request takes one request and an estimate of its tokens up front; once
+/// the response's usage is known, [`RateLimiter::record_usage`] settles the
+/// difference, so under-estimates slow down the following requests.
+pub struct RateLimiter {
+    state: Mutex<State>,
+}
+
+struct State {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    updated: Instant,
+    metrics: RateLimiterMetrics,
+}
+
+/// Counters of how much the limiter throttled so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RateLimiterMetrics {
+    /// Requests that went through the limiter.
+    pub requests: u64,
+    /// Requests that had to wait for capacity.
+    pub throttled: u64,
+    /// Total time spent waiting.
+    pub wait_time: Duration,
+}
+
+struct Bucket {
+    capacity: f64,
+    available: f64,
+    per_second: f64,
+}
+
+impl Bucket {
+    fn per_minute(limit: u32) -> Self {
+        let capacity = f64::from(limit.max(1));
+        Self {
+            capacity,
+            available: capacity,
+            per_second: capacity / 60.0,
+        }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.available =
+            (self.available + elapsed.as_secs_f64() * self.per_second).min(self.capacity);
+    }
+
+    /// Time until `amount` is available; requests larger than the bucket only
+    /// wait for it to be full.
+    fn wait_for(&self, amount: f64) -> Duration {
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.per_second)
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Limits requests and/or tokens per minute; `None` leaves that dimension unlimited.
+    pub fn new(requests_per_minute: Option<u32>, tokens_per_minute: Option<u32>) -> Self {
+        Self {
+            state: Mutex::new(State {
+                requests: requests_per_minute.map(Bucket::per_minute),
+                tokens: tokens_per_minute.map(Bucket::per_minute),
+                updated: Instant::now(),
+                metrics: RateLimiterMetrics::default(),
+            }),
+        }
+    }
+
+    /// Whether token estimates matter, i.e. a tokens-per-minute limit is set.
+    pub fn limits_tokens(&self) -> bool {
+        self.state.lock().unwrap().tokens.is_some()
+    }
+
+    /// Waits until a request using an estimated number of tokens may be sent.
+    pub async fn acquire(&self, estimated_tokens: u32) {
+        let started = Instant::now();
+        let mut throttled = false;
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                state.refill();
+                let tokens = f64::from(estimated_tokens);
+                let wait = [
+                    state.requests.as_ref().map(|bucket| bucket.wait_for(1.0)),
+                    state.tokens.as_ref().map(|bucket| bucket.wait_for(tokens)),
+                ]
+                .into_iter()
+                .flatten()
+                .max()
+                .unwrap_or_default();
+
+                if wait.is_zero() {
+                    if let Some(bucket) = &mut state.requests {
+                        bucket.available -= 1.0;
+                    }
+                    if let Some(bucket) = &mut state.tokens {
+                        bucket.available -= tokens;
+                    }
+                    state.metrics.requests += 1;
+                    if throttled {
+                        state.metrics.throttled += 1;
+                        state.metrics.wait_time += started.elapsed();
+                    }
+                    return;
+                }
+                wait
+            };
+            throttled = true;
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Settles the difference between the estimate a request was admitted with
+    /// and the tokens it actually used.
+    pub fn record_usage(&self, estimated_tokens: u32, actual_tokens: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.refill();
+        if let Some(bucket) = &mut state.tokens {
+            bucket.available -= f64::from(actual_tokens) - f64::from(estimated_tokens);
+        }
+    }
+
+    pub fn metrics(&self) -> RateLimiterMetrics {
+        self.state.lock().unwrap().metrics
+    }
+}
+
+impl State {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now - self.updated;
+        self.updated = now;
+        for bucket in [&mut self.requests, &mut self.tokens].into_iter().flatten() {
+            bucket.refill(elapsed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn it_should_throttle_requests_per_minute() {
+        let limiter = RateLimiter::new(Some(60), None);
+        let started = Instant::now();
+
+        for _ in 0..62 {
+            limiter.acquire(0).await;
+        }
+
+        // The first 60 pass immediately, then one request per second.
+        assert_eq!(started.elapsed().as_secs(), 2);
+        let metrics = limiter.metrics();
+        assert_eq!(metrics.requests, 62);
+        assert_eq!(metrics.throttled, 2);
+        assert_eq!(metrics.wait_time.as_secs(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_should_charge_actual_token_usage() {
+        let limiter = RateLimiter::new(None, Some(600));
+        let started = Instant::now();
+
+        limiter.acquire(100).await;
+        // The request used 200 tokens more than estimated: 300 are left, so
+        // the next 400 tokens are available after 10 seconds.
+        limiter.record_usage(100, 300);
+        limiter.acquire(400).await;
+
+        assert_eq!(started.elapsed().as_secs(), 10);
+        assert_eq!(limiter.metrics().throttled, 1);
+    }
+}
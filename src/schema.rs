@@ -0,0 +1,175 @@
+use crate::error::Error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Subset of the OpenAPI schema used for structured output, e.g.
+/// `{"type": "OBJECT", "properties": {"name": {"type": "STRING"}}}`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schema {
+    pub r#type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nullable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r#enum: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub properties: Option<HashMap<String, Schema>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Box<Schema>>,
+}
+
+impl Schema {
+    /// Checks that the value matches the schema, describing the first mismatch
+    /// with its location, e.g. `$.items[2].name: expected STRING, got number`.
+    pub fn validate(&self, value: &Value) -> Result<(), String> {
+        self.validate_at("$", value)
+    }
+
+    fn validate_at(&self, path: &str, value: &Value) -> Result<(), String> {
+        if value.is_null() {
+            return match self.nullable {
+                Some(true) => Ok(()),
+                _ => Err(format!("{}: expected {}, got null", path, self.type_name())),
+            };
+        }
+
+        let matches = match self.type_name().as_str() {
+            "STRING" => value.is_string(),
+            "NUMBER" => value.is_number(),
+            "INTEGER" => value.is_i64() || value.is_u64(),
+            "BOOLEAN" => value.is_boolean(),
+            "ARRAY" => value.is_array(),
+            "OBJECT" => value.is_object(),
+            other => return Err(format!("{}: unsupported schema type {}", path, other)),
+        };
+        if !matches {
+            return Err(format!(
+                "{}: expected {}, got {}",
+                path,
+                self.type_name(),
+                json_type(value)
+            ));
+        }
+
+        if let (Some(allowed), Value::String(s)) = (&self.r#enum, value) {
+            if !allowed.contains(s) {
+                return Err(format!(
+                    "{}: {:?} is not one of {}",
+                    path,
+                    s,
+                    allowed.join(", ")
+                ));
+            }
+        }
+
+        if let (Some(items), Value::Array(values)) = (&self.items, value) {
+            for (i, item) in values.iter().enumerate() {
+                items.validate_at(&format!("{}[{}]", path, i), item)?;
+            }
+        }
+
+        if let Value::Object(object) = value {
+            for name in self.required.iter().flatten() {
+                if !object.contains_key(name) {
+                    return Err(format!("{}: missing required property {}", path, name));
+                }
+            }
+            if let Some(properties) = &self.properties {
+                for (name, property) in object {
+                    if let Some(schema) = properties.get(name) {
+                        schema.validate_at(&format!("{}.{}", path, name), property)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn type_name(&self) -> String {
+        self.r#type.to_uppercase()
+    }
+}
+
+/// Parses a JSON response text, validating it against the schema (if any)
+/// before deserializing it.
+pub fn parse_json<T: DeserializeOwned>(text: &str, schema: Option<&Schema>) -> Result<T, Error> {
+    let value: Value = serde_json::from_str(text)
+        .map_err(|err| Error::Schema(format!("response is not valid JSON: {}", err)))?;
+    if let Some(schema) = schema {
+        schema.validate(&value).map_err(Error::Schema)?;
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe_schema() -> Schema {
+        serde_json::from_value(json!({
+            "type": "ARRAY",
+            "items": {
+                "type": "OBJECT",
+                "properties": {
+                    "name": {"type": "STRING"},
+                    "servings": {"type": "INTEGER"},
+                    "difficulty": {"type": "STRING", "enum": ["easy", "hard"]},
+                    "notes": {"type": "STRING", "nullable": true}
+                },
+                "required": ["name", "servings"]
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn it_should_accept_matching_value() {
+        let value = json!([
+            {"name": "Pancakes", "servings": 4, "difficulty": "easy", "notes": null},
+            {"name": "Soufflé", "servings": 2}
+        ]);
+        assert_eq!(recipe_schema().validate(&value), Ok(()));
+    }
+
+    #[test]
+    fn it_should_report_mismatch_with_path() {
+        let schema = recipe_schema();
+        assert_eq!(
+            schema.validate(&json!([{"name": "Pancakes", "servings": "four"}])),
+            Err("$[0].servings: expected INTEGER, got string".to_string())
+        );
+        assert_eq!(
+            schema.validate(&json!([{"name": "Pancakes"}])),
+            Err("$[0]: missing required property servings".to_string())
+        );
+        assert_eq!(
+            schema.validate(&json!([{"name": "Pancakes", "servings": 1, "difficulty": "medium"}])),
+            Err("$[0].difficulty: \"medium\" is not one of easy, hard".to_string())
+        );
+        assert_eq!(
+            schema.validate(&json!({"name": "Pancakes"})),
+            Err("$: expected ARRAY, got object".to_string())
+        );
+    }
+}
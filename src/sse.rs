@@ -0,0 +1,166 @@
+//! Incremental parser for Server-Sent Events, the format of
+//! `streamGenerateContent?alt=sse` responses.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Event {
+    /// Event type, if not the default `message`.
+    pub event: Option<String>,
+    /// Data lines joined with `\n`.
+    pub data: String,
+    pub id: Option<String>,
+}
+
+/// Splits a byte stream into events, whatever the boundaries of the chunks it
+/// arrives in (even inside a line or a UTF-8 character).
+#[derive(Debug)]
+pub struct EventParser {
+    line: Vec<u8>,
+    event: Event,
+    has_data: bool,
+    /// Whether the first line, which may start with a byte order mark, was read.
+    started: bool,
+    /// The last line ended with `\r`, so a `\n` right after it is part of that line break.
+    after_cr: bool,
+    max_size: usize,
+}
+
+impl EventParser {
+    /// Parser rejecting events larger than `max_size` bytes.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            line: Vec::new(),
+            event: Event::default(),
+            has_data: false,
+            started: false,
+            after_cr: false,
+            max_size,
+        }
+    }
+
+    /// Parses the next bytes of the stream, returning the events they complete.
+    pub fn push(&mut self, bytes: &[u8]) -> Result<Vec<Event>, String> {
+        let mut events = Vec::new();
+        for &byte in bytes {
+            if std::mem::take(&mut self.after_cr) && byte == b'\n' {
+                continue;
+            }
+            match byte {
+                b'\r' | b'\n' => {
+                    self.after_cr = byte == b'\r';
+                    let line = std::mem::take(&mut self.line);
+                    events.extend(self.process_line(&line));
+                }
+                _ => self.line.push(byte),
+            }
+        }
+
+        if self.line.len() + self.event.data.len() > self.max_size {
+            return Err(format!("event larger than {} bytes", self.max_size));
+        }
+        Ok(events)
+    }
+
+    /// Ends the stream. Unlike browsers, the last event is kept even if the
+    /// blank line ending it is missing.
+    pub fn finish(&mut self) -> Option<Event> {
+        let line = std::mem::take(&mut self.line);
+        if !line.is_empty() {
+            self.process_line(&line);
+        }
+        self.process_line(b"")
+    }
+
+    fn process_line(&mut self, line: &[u8]) -> Option<Event> {
+        let line = String::from_utf8_lossy(line);
+        let mut line = line.as_ref();
+        if !self.started {
+            self.started = true;
+            line = line.strip_prefix('\u{feff}').unwrap_or(line);
+        }
+
+        if line.is_empty() {
+            let event = std::mem::take(&mut self.event);
+            return std::mem::take(&mut self.has_data).then_some(event);
+        }
+        // Comment, e.g. a keep-alive.
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => {
+                if self.has_data {
+                    self.event.data.push('\n');
+                }
+                self.event.data.push_str(value);
+                self.has_data = true;
+            }
+            "event" => self.event.event = Some(value.to_string()),
+            "id" if !value.contains('\0') => self.event.id = Some(value.to_string()),
+            // `retry` only matters to reconnecting clients.
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STREAM: &str = "\u{feff}: keep-alive\r\n\
+        data: {\"text\": \"h\u{e9}llo\"}\r\n\r\n\
+        event: update\nid: 7\ndata: first line\ndata:second line\n\n\
+        retry: 1000\n\n\
+        data: {\"text\": \"end\"}\r\r";
+
+    fn expected() -> Vec<Event> {
+        vec![
+            Event {
+                data: "{\"text\": \"h\u{e9}llo\"}".to_string(),
+                ..Default::default()
+            },
+            Event {
+                event: Some("update".to_string()),
+                data: "first line\nsecond line".to_string(),
+                id: Some("7".to_string()),
+            },
+            Event {
+                data: "{\"text\": \"end\"}".to_string(),
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn it_should_parse_events_across_chunk_boundaries() {
+        for chunk_size in [1, 2, 3, 7, STREAM.len()] {
+            let mut parser = EventParser::new(1024);
+            let mut events = Vec::new();
+            for chunk in STREAM.as_bytes().chunks(chunk_size) {
+                events.extend(parser.push(chunk).unwrap());
+            }
+            events.extend(parser.finish());
+            assert_eq!(events, expected(), "chunks of {} bytes", chunk_size);
+        }
+    }
+
+    #[test]
+    fn it_should_keep_last_event_without_blank_line() {
+        let mut parser = EventParser::new(1024);
+        assert_eq!(parser.push(b"data: a\n\ndata: b").unwrap().len(), 1);
+        assert_eq!(parser.finish().unwrap().data, "b");
+        assert!(parser.finish().is_none());
+    }
+
+    #[test]
+    fn it_should_reject_oversized_events() {
+        let mut parser = EventParser::new(16);
+        assert!(parser.push(b"data: 0123").is_ok());
+        assert!(parser.push(b"456789abc").is_err());
+    }
+}
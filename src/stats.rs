@@ -0,0 +1,127 @@
+use gemini::UsageMetadata;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+/// Wall-clock timing of a generation, including any rounds of tool calls.
+pub struct Stats {
+    start: Instant,
+    first_chunk: Option<Duration>,
+    last_chunk: Option<Duration>,
+    latency: Option<Duration>,
+    output_tokens: u32,
+}
+
+impl Stats {
+    pub fn start() -> Self {
+        Self {
+            start: Instant::now(),
+            first_chunk: None,
+            last_chunk: None,
+            latency: None,
+            output_tokens: 0,
+        }
+    }
+
+    /// Records the arrival of a chunk.
+    pub fn chunk(&mut self) {
+        let elapsed = self.start.elapsed();
+        self.first_chunk.get_or_insert(elapsed);
+        self.last_chunk = Some(elapsed);
+    }
+
+    /// Counts the generated tokens of one response, thoughts included.
+    pub fn add_usage(&mut self, usage: &UsageMetadata) {
+        let tokens =
+            usage.candidates_token_count.unwrap_or(0) + usage.thoughts_token_count.unwrap_or(0);
+        self.output_tokens += tokens.max(0) as u32;
+    }
+
+    pub fn finish(&mut self) {
+        self.latency = Some(self.start.elapsed());
+    }
+
+    /// Output tokens over the time between the first and the last chunk, or
+    /// over the whole latency when the response came in one piece.
+    fn tokens_per_second(&self) -> Option<f64> {
+        let latency = self.latency?;
+        let streaming = match (self.first_chunk, self.last_chunk) {
+            (Some(first), Some(last)) => last - first,
+            _ => Duration::ZERO,
+        };
+        let elapsed = if streaming.is_zero() {
+            latency
+        } else {
+            streaming
+        };
+        (self.output_tokens > 0 && !elapsed.is_zero())
+            .then(|| f64::from(self.output_tokens) / elapsed.as_secs_f64())
+    }
+
+    /// Fields for the `meta` section of the log.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "timeToFirstTokenMs": self.first_chunk.map(|time| time.as_millis() as u64),
+            "latencyMs": self.latency.map(|time| time.as_millis() as u64),
+            "outputTokens": self.output_tokens,
+            "tokensPerSecond": self.tokens_per_second().map(|rate| (rate * 10.0).round() / 10.0),
+        })
+    }
+
+    /// One line for --stats, e.g. `first token 0.42s, total 3.10s, 120 tokens (44.7 tokens/s)`.
+    pub fn summary(&self) -> String {
+        let seconds = |time: Option<Duration>| match time {
+            Some(time) => format!("{:.2}s", time.as_secs_f64()),
+            None => "-".to_string(),
+        };
+        let mut summary = format!(
+            "first token {}, total {}, {} tokens",
+            seconds(self.first_chunk),
+            seconds(self.latency),
+            self.output_tokens
+        );
+        if let Some(rate) = self.tokens_per_second() {
+            summary.push_str(&format!(" ({:.1} tokens/s)", rate));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(first_chunk: u64, last_chunk: u64, latency: u64, output_tokens: u32) -> Stats {
+        Stats {
+            start: Instant::now(),
+            first_chunk: Some(Duration::from_millis(first_chunk)),
+            last_chunk: Some(Duration::from_millis(last_chunk)),
+            latency: Some(Duration::from_millis(latency)),
+            output_tokens,
+        }
+    }
+
+    #[test]
+    fn it_should_rate_tokens_between_first_and_last_chunk() {
+        let stats = stats(500, 2500, 2600, 100);
+        assert_eq!(stats.tokens_per_second(), Some(50.0));
+        assert_eq!(
+            stats.to_json(),
+            json!({
+                "timeToFirstTokenMs": 500,
+                "latencyMs": 2600,
+                "outputTokens": 100,
+                "tokensPerSecond": 50.0,
+            })
+        );
+        assert_eq!(
+            stats.summary(),
+            "first token 0.50s, total 2.60s, 100 tokens (50.0 tokens/s)"
+        );
+    }
+
+    #[test]
+    fn it_should_rate_tokens_of_a_single_chunk_over_the_latency() {
+        assert_eq!(stats(2000, 2000, 2000, 100).tokens_per_second(), Some(50.0));
+        assert_eq!(stats(2000, 2000, 2000, 0).tokens_per_second(), None);
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Extensions tried when a template is referenced without one.
+const EXTENSIONS: [&str; 2] = ["md", "txt"];
+
+/// Finds a template by file path, or by name in the templates directory.
+pub fn find(name: &str, dir: &Path) -> Result<PathBuf, String> {
+    let path = Path::new(name);
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+
+    let candidates = std::iter::once(dir.join(name)).chain(
+        EXTENSIONS
+            .iter()
+            .map(|ext| dir.join(format!("{}.{}", name, ext))),
+    );
+    for candidate in candidates {
+        if candidate.is_file() {
+            return Ok(candidate);
+        }
+    }
+
+    Err(format!("Template {} not found in {}", name, dir.display()))
+}
+
+/// Parses a `--var key=value` argument.
+pub fn parse_var(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected key=value, got {}", arg)),
+    }
+}
+
+/// Fills the `{{name}}` placeholders of a template: `{{file:path}}` with the
+/// contents of the file, `{{stdin}}` with standard input (read only if used)
+/// and any other name with its variable. Fails listing all missing variables.
+pub fn render(
+    template: &str,
+    vars: &HashMap<String, String>,
+    read_stdin: impl FnOnce() -> std::io::Result<String>,
+) -> Result<String, String> {
+    let mut read_stdin = Some(read_stdin);
+    let mut stdin: Option<String> = None;
+    let mut missing: Vec<&str> = Vec::new();
+    let mut output = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find("}}") else {
+            return Err("unclosed {{ in template".to_string());
+        };
+        let name = rest[start + 2..start + end].trim();
+        rest = &rest[start + end + 2..];
+
+        if let Some(path) = name.strip_prefix("file:") {
+            let path = path.trim();
+            let contents = fs::read_to_string(path)
+                .map_err(|err| format!("Failed to read {}: {}", path, err))?;
+            output.push_str(&contents);
+        } else if let Some(value) = vars.get(name) {
+            output.push_str(value);
+        } else if name == "stdin" {
+            if let Some(read) = read_stdin.take() {
+                let input = read().map_err(|err| format!("Failed to read stdin: {}", err))?;
+                stdin = Some(input.trim_end().to_string());
+            }
+            output.push_str(stdin.as_deref().unwrap_or_default());
+        } else if !missing.contains(&name) {
+            missing.push(name);
+        }
+    }
+    output.push_str(rest);
+
+    if !missing.is_empty() {
+        return Err(format!(
+            "Missing template variables: {} (set them with --var name=value)",
+            missing.join(", ")
+        ));
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn it_should_fill_variables_and_stdin() {
+        let rendered = render(
+            "Review this {{ lang }} diff:\n{{stdin}}\nAgain: {{stdin}}",
+            &vars(&[("lang", "Rust")]),
+            || Ok("+ fn main() {}\n".to_string()),
+        );
+        assert_eq!(
+            rendered,
+            Ok("Review this Rust diff:\n+ fn main() {}\nAgain: + fn main() {}".to_string())
+        );
+    }
+
+    #[test]
+    fn it_should_list_missing_variables() {
+        let rendered = render("{{a}} {{b}} {{a}} {{c}}", &vars(&[("b", "x")]), || {
+            panic!("stdin is not used")
+        });
+        assert_eq!(
+            rendered,
+            Err("Missing template variables: a, c (set them with --var name=value)".to_string())
+        );
+        assert!(render("{{a", &vars(&[]), || Ok(String::new())).is_err());
+    }
+
+    #[test]
+    fn it_should_parse_vars() {
+        assert_eq!(
+            parse_var("lang=Rust = 2021"),
+            Ok(("lang".to_string(), "Rust = 2021".to_string()))
+        );
+        assert!(parse_var("lang").is_err());
+        assert!(parse_var("=Rust").is_err());
+    }
+}
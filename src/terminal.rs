@@ -0,0 +1,81 @@
+use atty::Stream;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Asks a yes/no question on the terminal, even when stdin is piped; anything
+/// but `y`/`yes` (or no terminal at all) is a no.
+pub fn confirm(question: &str) -> bool {
+    eprint!("{} [y/N] ", question);
+    let _ = io::stderr().flush();
+
+    let mut answer = String::new();
+    let read = match File::open("/dev/tty") {
+        Ok(tty) => BufReader::new(tty).read_line(&mut answer),
+        Err(_) => io::stdin().lock().read_line(&mut answer),
+    };
+    if read.is_err() {
+        eprintln!();
+        return false;
+    }
+
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Prints a status line (tool calls, --stats) on stderr, where the spinner
+/// runs, keeping it out of the response on stdout.
+pub fn status(message: &str) {
+    eprintln!("{}", message);
+}
+
+/// Animation on stderr, if it is a terminal, until stopped or dropped.
+pub struct Spinner {
+    /// Set on stop, so that the task can't draw a frame after the line was cleared.
+    stopped: Arc<Mutex<bool>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    pub fn start(message: &str) -> Self {
+        let stopped = Arc::new(Mutex::new(false));
+        let task = atty::is(Stream::Stderr).then(|| {
+            let stopped = stopped.clone();
+            let message = message.to_string();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_millis(80));
+                for frame in SPINNER_FRAMES.iter().cycle() {
+                    interval.tick().await;
+                    let stopped = stopped.lock().unwrap();
+                    if *stopped {
+                        break;
+                    }
+                    eprint!("\r{} {}", frame, message);
+                    let _ = io::stderr().flush();
+                }
+            })
+        });
+        Self { stopped, task }
+    }
+
+    pub fn stop(&mut self) {
+        let Some(task) = self.task.take() else {
+            return;
+        };
+        *self.stopped.lock().unwrap() = true;
+        task.abort();
+        eprint!("\r\x1b[2K");
+        let _ = io::stderr().flush();
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,457 @@
+//! Tools the model can call: the built-in ones of `--tools`, confined to a
+//! working directory, and those of the MCP servers enabled with `--mcp`.
+//!
+//! There is no real sandbox. The file tools only resolve paths inside the
+//! working directory, but a command run by `run_command` has the user's full
+//! permissions: an allowed program can still read or write anything it is
+//! able to, e.g. through its own options or configuration.
+
+use crate::{extract::check_relative, mcp::McpServer, rag::collect_files};
+use gemini::{FunctionDeclaration, FunctionParameters, FunctionParametersProperty, Tools};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Longest tool output returned to the model, in bytes.
+const MAX_OUTPUT: usize = 20_000;
+
+/// Maximum number of matching lines returned by `grep`.
+const MAX_MATCHES: usize = 100;
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Characters a shell would interpret (chaining, substitution, quoting,
+/// globbing), which an allowed command, run without a shell, must not contain.
+const SHELL_SYNTAX: &[char] = &[
+    ';', '&', '|', '$', '`', '<', '>', '(', ')', '\n', '\'', '"', '\\', '*', '?', '[', '{', '~',
+    '#',
+];
+
+const BUILTIN_TOOLS: &[&str] = &["read_file", "list_dir", "grep", "run_command"];
+
+pub struct Toolbox {
+    root: PathBuf,
+    allowed_commands: Vec<String>,
+    confirm: fn(&str) -> bool,
+    builtin: bool,
+    servers: Vec<McpServer>,
+    transcript: Vec<Value>,
+}
+
+impl Toolbox {
+    /// Tools working in `root`; commands run without a shell and without asking
+    /// if they start with one of `allowed_commands` and only have plain relative
+    /// arguments, otherwise with `sh -c` once `confirm` returns true.
+    pub fn new(
+        root: &Path,
+        allowed_commands: Vec<String>,
+        confirm: fn(&str) -> bool,
+    ) -> Result<Self, String> {
+        let root = root
+            .canonicalize()
+            .map_err(|err| format!("Invalid working directory {}: {}", root.display(), err))?;
+        Ok(Self {
+            root,
+            allowed_commands,
+            confirm,
+            builtin: true,
+            servers: Vec::new(),
+            transcript: Vec::new(),
+        })
+    }
+
+    /// Whether to offer the built-in tools, e.g. not when only MCP tools are wanted.
+    pub fn builtin(mut self, enabled: bool) -> Self {
+        self.builtin = enabled;
+        self
+    }
+
+    /// Offers the tools of an MCP server; their names must be unique.
+    pub fn add_server(&mut self, server: McpServer) -> Result<(), String> {
+        for tool in server.tool_names() {
+            if self.builtin && BUILTIN_TOOLS.contains(&tool) {
+                return Err(format!(
+                    "MCP server {}: tool {} conflicts with a built-in tool",
+                    server.name(),
+                    tool
+                ));
+            }
+            if let Some(other) = self.servers.iter().find(|other| other.has_tool(tool)) {
+                return Err(format!(
+                    "MCP servers {} and {} both provide a tool named {}",
+                    other.name(),
+                    server.name(),
+                    tool
+                ));
+            }
+        }
+        self.servers.push(server);
+        Ok(())
+    }
+
+    pub fn declarations(&self) -> Tools {
+        let mut declarations = if self.builtin {
+            builtin_declarations()
+        } else {
+            Vec::new()
+        };
+        for server in &self.servers {
+            declarations.extend(server.declarations());
+        }
+        Tools {
+            function_declarations: Some(declarations),
+        }
+    }
+
+    /// Runs a tool call, returning the function response for the model.
+    pub async fn call(&mut self, name: &str, args: &HashMap<String, Value>) -> Value {
+        let arg = |key: &str| args.get(key).and_then(Value::as_str);
+
+        let result = match name {
+            "read_file" if self.builtin => self.read_file(arg("path").unwrap_or_default()),
+            "list_dir" if self.builtin => self.list_dir(arg("path").unwrap_or(".")),
+            "grep" if self.builtin => self.grep(
+                arg("pattern").unwrap_or_default(),
+                arg("path").unwrap_or("."),
+            ),
+            "run_command" if self.builtin => {
+                self.run_command(arg("command").unwrap_or_default()).await
+            }
+            _ => self.call_server(name, args).await,
+        };
+        let response = match result {
+            Ok(output) => json!({ "output": truncate(output) }),
+            Err(err) => json!({ "error": err }),
+        };
+
+        self.transcript.push(json!({
+            "name": name,
+            "args": args,
+            "response": response,
+        }));
+        response
+    }
+
+    async fn call_server(
+        &mut self,
+        name: &str,
+        args: &HashMap<String, Value>,
+    ) -> Result<String, String> {
+        let confirm = self.confirm;
+        let server = self
+            .servers
+            .iter_mut()
+            .find(|server| server.has_tool(name))
+            .ok_or_else(|| format!("Unknown tool {}", name))?;
+        if !server.is_trusted(name)
+            && !confirm(&format!(
+                "Call {} of MCP server {} with {}?",
+                name,
+                server.name(),
+                json!(args)
+            ))
+        {
+            return Err("The user declined this call.".to_string());
+        }
+        server.call(name, args).await
+    }
+
+    /// The calls made so far with their responses, for the log.
+    pub fn transcript(&self) -> &[Value] {
+        &self.transcript
+    }
+
+    /// Resolves a path of an existing file or directory inside the root.
+    fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let resolved = self
+            .root
+            .join(path)
+            .canonicalize()
+            .map_err(|err| format!("{}: {}", path, err))?;
+        if !resolved.starts_with(&self.root) {
+            return Err(format!("{}: outside the working directory", path));
+        }
+        Ok(resolved)
+    }
+
+    fn relative<'a>(&self, path: &'a Path) -> std::borrow::Cow<'a, str> {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .to_string_lossy()
+    }
+
+    fn read_file(&self, path: &str) -> Result<String, String> {
+        fs::read_to_string(self.resolve(path)?).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    fn list_dir(&self, path: &str) -> Result<String, String> {
+        let dir = self.resolve(path)?;
+        let mut entries: Vec<String> = fs::read_dir(&dir)
+            .map_err(|err| format!("{}: {}", path, err))?
+            .filter_map(Result::ok)
+            .map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                match entry.file_type() {
+                    Ok(file_type) if file_type.is_dir() => format!("{}/", name),
+                    _ => name,
+                }
+            })
+            .collect();
+        entries.sort();
+        Ok(entries.join("\n"))
+    }
+
+    fn grep(&self, pattern: &str, path: &str) -> Result<String, String> {
+        if pattern.is_empty() {
+            return Err("pattern must not be empty".to_string());
+        }
+        let target = self.resolve(path)?;
+        let files = if target.is_dir() {
+            collect_files(&target).map_err(|err| format!("{}: {}", path, err))?
+        } else {
+            vec![target]
+        };
+
+        let mut matches = Vec::new();
+        for file in files {
+            // Skip binary files.
+            let Ok(text) = fs::read_to_string(&file) else {
+                continue;
+            };
+            for (i, line) in text.lines().enumerate() {
+                if line.contains(pattern) {
+                    matches.push(format!("{}:{}: {}", self.relative(&file), i + 1, line));
+                    if matches.len() == MAX_MATCHES {
+                        matches.push("(more matches omitted)".to_string());
+                        return Ok(matches.join("\n"));
+                    }
+                }
+            }
+        }
+        Ok(matches.join("\n"))
+    }
+
+    async fn run_command(&self, command: &str) -> Result<String, String> {
+        if command.trim().is_empty() {
+            return Err("command must not be empty".to_string());
+        }
+        let mut child = match self.allowed_argv(command) {
+            Some(argv) => {
+                let mut child = tokio::process::Command::new(argv[0]);
+                child.args(&argv[1..]);
+                child
+            }
+            None if (self.confirm)(&format!("Run `{}`?", command)) => {
+                let mut child = tokio::process::Command::new("sh");
+                child.arg("-c").arg(command);
+                child
+            }
+            None => return Err("The user declined to run this command.".to_string()),
+        };
+        let child = child.current_dir(&self.root).kill_on_drop(true).output();
+        let output = tokio::time::timeout(COMMAND_TIMEOUT, child)
+            .await
+            .map_err(|_| format!("timed out after {:?}", COMMAND_TIMEOUT))?
+            .map_err(|err| err.to_string())?;
+
+        Ok(format!(
+            "exit status: {}\nstdout:\n{}\nstderr:\n{}",
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+
+    /// The words of the command if it is one of the allowed ones, possibly
+    /// with more arguments, but without shell syntax and without absolute or
+    /// `..` paths (also as `--option=path`).
+    fn allowed_argv<'a>(&self, command: &'a str) -> Option<Vec<&'a str>> {
+        if command.contains(SHELL_SYNTAX) {
+            return None;
+        }
+        let argv: Vec<&str> = command.split_whitespace().collect();
+        let allowed = self.allowed_commands.iter().any(|allowed| {
+            let allowed: Vec<&str> = allowed.split_whitespace().collect();
+            !allowed.is_empty() && argv.starts_with(&allowed)
+        });
+        let relative = argv.iter().skip(1).all(|arg| {
+            let value = arg.split_once('=').map_or(*arg, |(_, value)| value);
+            check_relative(Path::new(arg)).is_ok() && check_relative(Path::new(value)).is_ok()
+        });
+        (allowed && relative).then_some(argv)
+    }
+}
+
+fn builtin_declarations() -> Vec<FunctionDeclaration> {
+    let declaration =
+        |name: &str, description: &str, params: &[(&str, &str)], required: &[&str]| {
+            FunctionDeclaration {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: FunctionParameters {
+                    r#type: "OBJECT".to_string(),
+                    properties: params
+                        .iter()
+                        .map(|(name, description)| {
+                            (
+                                name.to_string(),
+                                FunctionParametersProperty {
+                                    r#type: "STRING".to_string(),
+                                    description: description.to_string(),
+                                    ..Default::default()
+                                },
+                            )
+                        })
+                        .collect(),
+                    required: required.iter().map(|name| name.to_string()).collect(),
+                },
+            }
+        };
+
+    vec![
+        declaration(
+            "read_file",
+            "Reads a text file of the working directory.",
+            &[("path", "Path relative to the working directory")],
+            &["path"],
+        ),
+        declaration(
+            "list_dir",
+            "Lists the entries of a directory; directories end with a slash.",
+            &[("path", "Path relative to the working directory, default .")],
+            &[],
+        ),
+        declaration(
+            "grep",
+            "Finds the lines containing a text in the files under a path.",
+            &[
+                ("pattern", "Text to search for (case-sensitive)"),
+                ("path", "File or directory to search, default ."),
+            ],
+            &["pattern"],
+        ),
+        declaration(
+            "run_command",
+            "Runs a command in the working directory and returns its output. \
+                    The user may refuse to run it.",
+            &[(
+                "command",
+                "Command line; allowed commands run without a shell, others with sh -c",
+            )],
+            &["command"],
+        ),
+    ]
+}
+
+fn truncate(mut output: String) -> String {
+    if output.len() > MAX_OUTPUT {
+        let mut end = MAX_OUTPUT;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        output.truncate(end);
+        output.push_str("\n(output truncated)");
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workdir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("gemini-tools-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(
+            dir.join("src/main.rs"),
+            "fn main() {\n    println!(\"hi\");\n}\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    fn args(pairs: &[(&str, &str)]) -> HashMap<String, Value> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), json!(value)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn it_should_read_inside_the_working_directory_only() {
+        let dir = workdir("read");
+        let mut toolbox = Toolbox::new(&dir, Vec::new(), |_| false).unwrap();
+
+        let listing = toolbox.call("list_dir", &args(&[])).await;
+        assert_eq!(listing, json!({ "output": "src/" }));
+        let matches = toolbox.call("grep", &args(&[("pattern", "println")])).await;
+        assert_eq!(
+            matches,
+            json!({ "output": "src/main.rs:2:     println!(\"hi\");" })
+        );
+        let escape = toolbox.call("read_file", &args(&[("path", "../")])).await;
+        assert!(escape["error"]
+            .as_str()
+            .unwrap()
+            .contains("outside the working directory"));
+        assert_eq!(toolbox.transcript().len(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_should_confirm_commands_not_allowed() {
+        let dir = workdir("run");
+        let mut toolbox = Toolbox::new(&dir, vec!["ls".to_string()], |_| false).unwrap();
+
+        let allowed = toolbox
+            .call("run_command", &args(&[("command", "ls src")]))
+            .await;
+        assert_eq!(
+            allowed,
+            json!({ "output": "exit status: 0\nstdout:\nmain.rs\n\nstderr:\n" })
+        );
+        let chained = toolbox
+            .call("run_command", &args(&[("command", "ls; touch x")]))
+            .await;
+        assert_eq!(
+            chained,
+            json!({ "error": "The user declined to run this command." })
+        );
+        assert!(!dir.join("x").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_should_only_allow_plain_commands_with_relative_paths() {
+        let toolbox = Toolbox::new(
+            &std::env::temp_dir(),
+            vec!["cat".to_string(), "cargo test".to_string()],
+            |_| false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            toolbox.allowed_argv("cat  src/main.rs"),
+            Some(vec!["cat", "src/main.rs"])
+        );
+        assert!(toolbox.allowed_argv("cargo test --lib").is_some());
+        assert!(toolbox.allowed_argv("cargo build").is_none());
+        assert!(toolbox.allowed_argv("catalog").is_none());
+        assert!(toolbox.allowed_argv("cat /etc/shadow").is_none());
+        assert!(toolbox.allowed_argv("cat ../secret").is_none());
+        assert!(toolbox
+            .allowed_argv("cargo test --manifest-path=/x/Cargo.toml")
+            .is_none());
+        assert!(toolbox.allowed_argv("cat $(echo /etc/shadow)").is_none());
+        assert!(toolbox.allowed_argv("cat `echo /etc/shadow`").is_none());
+        assert!(toolbox.allowed_argv("cat '/etc/shadow'").is_none());
+    }
+}